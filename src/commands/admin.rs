@@ -0,0 +1,157 @@
+use framework::args::Args;
+use framework::command::{Command, CommandResult, Context};
+use framework::embed::Embed;
+use framework::permissions::Permission;
+use framework::registry::CommandRegistry;
+use framework::shutdown::{ShutdownKind, ShutdownRequest};
+use std::time::Duration;
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(Shutdown);
+    registry.register(Restart);
+    registry.register(Shards);
+}
+
+pub struct Shutdown;
+
+impl Command for Shutdown {
+    fn name(&self) -> &'static str {
+        "shutdown"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["quit"]
+    }
+
+    fn category(&self) -> &'static str {
+        "Admin"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Shut the bot down once running commands finish.")
+    }
+
+    fn permissions(&self) -> &'static [Permission] {
+        &[Permission::Owner]
+    }
+
+    fn exec(&self, ctx: &Context, _args: Args) -> CommandResult {
+        request_shutdown(ctx, ShutdownKind::Stop)
+    }
+}
+
+pub struct Restart;
+
+impl Command for Restart {
+    fn name(&self) -> &'static str {
+        "restart"
+    }
+
+    fn category(&self) -> &'static str {
+        "Admin"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Restart the bot once running commands finish, picking up any new \
+              configuration.")
+    }
+
+    fn permissions(&self) -> &'static [Permission] {
+        &[Permission::Owner]
+    }
+
+    fn exec(&self, ctx: &Context, _args: Args) -> CommandResult {
+        request_shutdown(ctx, ShutdownKind::Restart)
+    }
+}
+
+/// Whoever is running the bot confirms in this channel once it's safe to stop.
+fn request_shutdown(ctx: &Context, kind: ShutdownKind) -> CommandResult {
+    let request = ShutdownRequest {
+        kind: kind,
+        reply_to: Some((ctx.responder.clone(), ctx.message.channel_id)),
+    };
+    if ctx.dispatcher.shutdown().request(request) {
+        info!("{:?} requested by {}.", kind, ctx.message.author.id);
+    } else {
+        ctx.send_message("Already shutting down.")?;
+    }
+    Ok(())
+}
+
+pub struct Shards;
+
+impl Command for Shards {
+    fn name(&self) -> &'static str {
+        "shards"
+    }
+
+    fn category(&self) -> &'static str {
+        "Admin"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Show the state of each shard's connection to Discord.")
+    }
+
+    fn permissions(&self) -> &'static [Permission] {
+        &[Permission::Owner]
+    }
+
+    fn exec(&self, ctx: &Context, _args: Args) -> CommandResult {
+        let shards = ctx.dispatcher.shards();
+        if shards.count() == 0 {
+            ctx.send_message("Not connected to Discord.")?;
+            return Ok(());
+        }
+
+        let lines: Vec<String> = shards.iter()
+            .enumerate()
+            .map(|(id, status)| {
+                format!("`{}` {} for {}, {} servers, {} resumes, {} reconnects",
+                        id,
+                        status.state(),
+                        age_string(status.state_age()),
+                        status.servers(),
+                        status.resumes(),
+                        status.reconnects())
+            })
+            .collect();
+        let mut embed = Embed::new()
+            .title(&format!("{} shards", shards.count()))
+            .description(&lines.join("\n"));
+        if let Some(shard) = ctx.message.server_id.and_then(|s| shards.shard_for(s)) {
+            embed = embed.footer(&format!("This server is on shard {}.", shard));
+        }
+        ctx.send_embed(&embed)?;
+        Ok(())
+    }
+}
+
+/// `duration` to the nearest unit that matters, e.g. `3h 2m` or `45s`.
+fn age_string(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (days, hours, minutes) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn age_string_units() {
+        assert_eq!(age_string(Duration::from_secs(45)), "45s");
+        assert_eq!(age_string(Duration::from_secs(125)), "2m 5s");
+        assert_eq!(age_string(Duration::from_secs(3 * 3600 + 120 + 7)), "3h 2m");
+        assert_eq!(age_string(Duration::from_secs(2 * 86400 + 5 * 3600)), "2d 5h");
+    }
+}
@@ -1,14 +1,44 @@
 use chrono::{Datelike, Duration, Timelike};
 use chrono::prelude::{DateTime, Utc, Weekday};
-use discord::Discord;
-use discord::model::Message;
-use framework::args::Args;
+use config::FfxivConfig;
+use framework::args::{ArgError, Args, FromArgs};
+use framework::command::{Argument, Arity, Command, CommandError, CommandResult, Context};
+use framework::cooldown::{Bucket, Cooldown};
+use framework::embed::Embed;
+use framework::group::CommandGroup;
+use framework::registry::CommandRegistry;
+use metrics::Metrics;
 use reqwest;
 use scraper::{Html, Selector};
 use serde_json;
 use std::io::Read;
+use std::sync::Mutex;
+use std::time::{Duration as StdDuration, Instant};
+
+pub fn register(registry: &mut CommandRegistry, config: &FfxivConfig) {
+    let mut ffxiv = CommandGroup::new("ffxiv", "Final Fantasy XIV reset timers and events");
+    ffxiv.register(Resets);
+    ffxiv.register(Events { feed: TimersFeed::new(&config.timers_url, config.timers_cache_ttl()) });
+    registry.register_group(ffxiv);
+}
+
+pub struct Resets;
+
+impl Command for Resets {
+    fn name(&self) -> &'static str {
+        "resets"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Show how long until the daily/weekly resets in FF XIV")
+    }
+
+    fn exec(&self, ctx: &Context, _args: Args) -> CommandResult {
+        resets(ctx)
+    }
+}
 
-pub fn resets(bot: &Discord, msg: &Message, _args: Args) {
+fn resets(ctx: &Context) -> CommandResult {
     let now = Utc::now();
     let daily_reset = next_daily_reset(now);
     let weekly_reset = next_weekly_reset(now);
@@ -18,15 +48,12 @@ pub fn resets(bot: &Discord, msg: &Message, _args: Args) {
     let until_weekly = until_string(weekly_reset.signed_duration_since(now));
     let until_crafting = until_string(crafting_reset.signed_duration_since(now));
 
-    let _ = bot.send_embed(msg.channel_id, "", |embed| {
-        embed
-            .title("FF XIV Resets")
-            .fields(|f| {
-                        f.field("Daily", &until_daily, false)
-                            .field("Weekly", &until_weekly, false)
-                            .field("Crafting", &until_crafting, false)
-                    })
-    });
+    ctx.send_embed(&Embed::new()
+                        .title("FF XIV Resets")
+                        .field("Daily", &until_daily, false)
+                        .field("Weekly", &until_weekly, false)
+                        .field("Crafting", &until_crafting, false))?;
+    Ok(())
 }
 
 fn next_daily_reset(now: DateTime<Utc>) -> DateTime<Utc> {
@@ -148,63 +175,162 @@ fn until_string(until_duration: Duration) -> String {
     components.join(" ")
 }
 
-pub fn events(bot: &Discord, msg: &Message, _args: Args) {
+pub struct Events {
+    feed: TimersFeed,
+}
+
+impl Command for Events {
+    fn name(&self) -> &'static str {
+        "events"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("List known events in FF XIV")
+    }
+
+    fn arguments(&self) -> &'static [Argument] {
+        &[Argument {
+              name: "kind",
+              description: "Only list events of this kind, like `maintenance` or `campaign`.",
+              arity: Arity::Named {
+                  short: Some('k'),
+                  default: None,
+              },
+          },
+          Argument {
+              name: "upcoming",
+              description: "Only list events that haven't started yet.",
+              arity: Arity::Flag { short: Some('u') },
+          }]
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        &["ffxiv events", "ffxiv events --kind maintenance --upcoming", "ffxiv events -u"]
+    }
+
+    fn cooldown(&self) -> Option<Cooldown> {
+        // Each use fetches the timers and posts an embed per event.
+        Some(Cooldown {
+                 bucket: Bucket::Channel,
+                 uses: 2,
+                 per: StdDuration::from_secs(60),
+             })
+    }
+
+    fn exec(&self, ctx: &Context, args: Args) -> CommandResult {
+        events(ctx, &self.feed, args.parse()?)
+    }
+}
+
+struct EventsArgs {
+    kind: Option<String>,
+    upcoming: bool,
+}
+
+impl FromArgs for EventsArgs {
+    fn from_args(args: &Args) -> Result<EventsArgs, ArgError> {
+        Ok(EventsArgs {
+               kind: args.optional("kind")?,
+               upcoming: args.flag("upcoming"),
+           })
+    }
+}
+
+impl EventsArgs {
+    /// Whether `event` should be listed at `now`. Kinds match any word of the
+    /// event's kind, so `event` matches `event rising`.
+    fn wanted(&self, event: &FFXIVEvent, now: DateTime<Utc>) -> bool {
+        if event.end <= now || (self.upcoming && event.start <= now) {
+            return false;
+        }
+
+        match self.kind {
+            Some(ref kind) => {
+                event.kind
+                    .split_whitespace()
+                    .any(|word| word.eq_ignore_ascii_case(kind))
+            }
+            None => true,
+        }
+    }
+}
+
+fn events(ctx: &Context, feed: &TimersFeed, args: EventsArgs) -> CommandResult {
     let now = Utc::now();
 
-    let events = get_events();
+    let events = feed.events(ctx.dispatcher.metrics());
     match events {
         Ok(even) => {
             for event in even.iter() {
-                if event.end > now {
-                    let _ = bot.send_embed(msg.channel_id, "", |embed| {
-                        embed
-                            .title(&event.name())
-                            .fields(|f| {
-                                let mut fields = f;
-                                fields = fields.field("More information", &event.url(), false);
-                                if event.start > now {
-                                    fields = fields
-                                        .field("Start",
-                                               &until_string(event
-                                                                 .start
-                                                                 .signed_duration_since(now)),
-                                               false);
-                                }
-                                fields = fields
-                                    .field("End",
-                                           &until_string(event.end.signed_duration_since(now)),
-                                           false);
-                                if let Some(ref info) = event.info {
-                                    fields = fields.field("Info", info, false);
-                                }
-                                fields
-                            })
-                    });
+                if args.wanted(event, now) {
+                    let mut embed = Embed::new()
+                        .title(&event.name())
+                        .field("More information", &event.url(), false);
+                    if event.start > now {
+                        embed = embed.field("Start",
+                                            &until_string(event.start.signed_duration_since(now)),
+                                            false);
+                    }
+                    embed = embed.field("End",
+                                        &until_string(event.end.signed_duration_since(now)),
+                                        false);
+                    if let Some(ref info) = event.info {
+                        embed = embed.field("Info", info, false);
+                    }
+                    ctx.send_embed(&embed)?;
                 }
             }
         }
-        Err(e) => {
-            println!("{}", e);
-        }
+        Err(e) => return Err(CommandError::Upstream(e)),
     };
+
+    Ok(())
 }
 
-fn get_events() -> Result<Vec<FFXIVEvent>, String> {
-    let json = retrieve_event_json();
-    let timers;
-    match json {
-        Ok(j) => timers = parse_event_json(&j),
-        Err(e) => return Err(format!("{}", e)),
-    };
+/// What the timers feed is called in metrics.
+const TIMERS_CACHE: &'static str = "ffxiv_timers";
 
-    match timers {
-        Ok(t) => Ok(t.events),
-        Err(e) => Err(format!("{}", e)),
+/// The feed of FF XIV events, fetched at most once per `ttl`.
+struct TimersFeed {
+    url: String,
+    ttl: StdDuration,
+    cached: Mutex<Option<(Instant, Vec<FFXIVEvent>)>>,
+}
+
+impl TimersFeed {
+    fn new(url: &str, ttl: StdDuration) -> TimersFeed {
+        TimersFeed {
+            url: url.to_string(),
+            ttl: ttl,
+            cached: Mutex::new(None),
+        }
+    }
+
+    fn events(&self, metrics: &Metrics) -> Result<Vec<FFXIVEvent>, String> {
+        if let Some((fetched, ref events)) = *self.cached.lock().unwrap() {
+            if fetched.elapsed() < self.ttl {
+                debug!("Using cached events from {}", self.url);
+                metrics.record_cache_lookup(TIMERS_CACHE, true);
+                return Ok(events.clone());
+            }
+        }
+
+        debug!("Fetching events from {}", self.url);
+        metrics.record_cache_lookup(TIMERS_CACHE, false);
+        let result = retrieve_event_json(&self.url).and_then(|json| parse_event_json(&json));
+        let events = match result {
+            Ok(timers) => timers.events,
+            Err(e) => {
+                metrics.record_upstream_error(TIMERS_CACHE);
+                return Err(e);
+            }
+        };
+        *self.cached.lock().unwrap() = Some((Instant::now(), events.clone()));
+        Ok(events)
     }
 }
 
-fn retrieve_event_json() -> Result<String, String> {
-    let url = "http://www.xenoveritas.org/static/ffxiv/timers.json";
+fn retrieve_event_json(url: &str) -> Result<String, String> {
     let mut resp = match reqwest::get(url) {
         Ok(r) => r,
         Err(e) => return Err(format!("{}", e)),
@@ -221,13 +347,13 @@ fn retrieve_event_json() -> Result<String, String> {
     Ok(content)
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 struct FFXIVTimers {
     #[serde(rename = "timers")]
     pub events: Vec<FFXIVEvent>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 struct FFXIVEvent {
     #[serde(rename = "name")]
     pub name_html: String,
@@ -741,4 +867,39 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    fn event(kind: &str, start: &str, end: &str) -> FFXIVEvent {
+        FFXIVEvent {
+            name_html: "<a href=\"https://example.com/\">Event</a>".to_string(),
+            kind: kind.to_string(),
+            start: DateTime::parse_from_rfc3339(start).unwrap().with_timezone(&Utc),
+            end: DateTime::parse_from_rfc3339(end).unwrap().with_timezone(&Utc),
+            info: None,
+        }
+    }
+
+    #[test]
+    fn events_filtered_by_kind_and_start() {
+        let now = DateTime::parse_from_rfc3339("2017-09-05T00:00:00Z").unwrap().with_timezone(&Utc);
+        let rising = event("event rising", "2017-08-26T15:00:00Z", "2017-09-14T14:59:00Z");
+        let maintenance = event("maintenance", "2017-09-15T06:00:00Z", "2017-09-15T09:00:00Z");
+        let over = event("campaign", "2017-07-01T07:00:00Z", "2017-09-01T06:59:00Z");
+        let all = EventsArgs {
+            kind: None,
+            upcoming: false,
+        };
+        let by_kind = EventsArgs {
+            kind: Some("Event".to_string()),
+            upcoming: false,
+        };
+        let upcoming = EventsArgs {
+            kind: None,
+            upcoming: true,
+        };
+
+        assert!(all.wanted(&rising, now) && all.wanted(&maintenance, now));
+        assert!(!all.wanted(&over, now));
+        assert!(by_kind.wanted(&rising, now) && !by_kind.wanted(&maintenance, now));
+        assert!(!upcoming.wanted(&rising, now) && upcoming.wanted(&maintenance, now));
+    }
 }
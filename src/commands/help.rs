@@ -0,0 +1,399 @@
+use framework::args::Args;
+use framework::command::{self, Argument, Arity, Command, CommandError, CommandResult, Context};
+use framework::embed::Embed;
+use framework::group::CommandGroup;
+use framework::registry::{CommandRegistry, Entry, Lookup};
+
+/// Discord allows at most 25 fields in an embed.
+const MAX_FIELDS_PER_PAGE: usize = 25;
+/// Discord allows at most 1024 characters in an embed field's value.
+const MAX_FIELD_LENGTH: usize = 1024;
+/// Discord allows 6000 characters across a whole embed. Leave room for the
+/// title and footer.
+const MAX_PAGE_LENGTH: usize = 5000;
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(Help);
+}
+
+pub struct Help;
+
+impl Command for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("List commands, or show the details of one command or group.")
+    }
+
+    fn arguments(&self) -> &'static [Argument] {
+        &[Argument {
+              name: "command",
+              description: "A command or group to show the details of, or a page of the command list.",
+              arity: Arity::Rest,
+          }]
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        &["help", "help 2", "help ffxiv", "help ffxiv events"]
+    }
+
+    fn exec(&self, ctx: &Context, args: Args) -> CommandResult {
+        let prefix = ctx.prefix;
+        let registry = ctx.dispatcher.registry();
+        let query = args.optional::<String>("command")?.unwrap_or_default();
+
+        if query.is_empty() || query.parse::<usize>().is_ok() {
+            let pages = paginate(listing_sections(prefix, registry));
+            let page_number = query.parse::<usize>().unwrap_or(1);
+            if page_number == 0 || page_number > pages.len() {
+                let only = if pages.len() == 1 {
+                    "There's only 1 page of commands.".to_string()
+                } else {
+                    format!("There are only {} pages of commands.", pages.len())
+                };
+                return Err(CommandError::BadArguments(only));
+            }
+            let footer = format!("Page {} of {}. Use {}help <command> for details, or {}help <page> for another page.",
+                                 page_number,
+                                 pages.len(),
+                                 prefix,
+                                 prefix);
+            return send_page(ctx, "Commands", None, &pages[page_number - 1], Some(&footer));
+        }
+
+        match registry.find(&query) {
+            Some(Lookup::Command { ref path, command, args: "" }) => {
+                let pages = paginate(command_sections(prefix, path, command));
+                for page in &pages {
+                    send_page(ctx,
+                              &format!("{}{}", prefix, path),
+                              command.description(),
+                              page,
+                              None)?;
+                }
+                Ok(())
+            }
+            Some(Lookup::Group { ref path, group, args: "" }) => {
+                let pages = paginate(group_sections(prefix, path, group));
+                for page in &pages {
+                    send_page(ctx,
+                              &format!("{}{}", prefix, path),
+                              group.description(),
+                              page,
+                              None)?;
+                }
+                Ok(())
+            }
+            _ => {
+                Err(CommandError::BadArguments(format!("There's no command named `{}`.", query)))
+            }
+        }
+    }
+}
+
+/// A titled list of lines, which becomes one or more embed fields.
+struct Section {
+    title: String,
+    lines: Vec<String>,
+}
+
+type Page = Vec<(String, String)>;
+
+fn send_page(ctx: &Context,
+             title: &str,
+             description: Option<&str>,
+             page: &Page,
+             footer: Option<&str>)
+             -> CommandResult {
+    let mut embed = Embed::new().title(title);
+    if let Some(description) = description {
+        embed = embed.description(description);
+    }
+    if let Some(footer) = footer {
+        embed = embed.footer(footer);
+    }
+    for &(ref name, ref value) in page {
+        embed = embed.field(name, value, false);
+    }
+    ctx.send_embed(&embed)?;
+    Ok(())
+}
+
+/// Every top-level command, grouped by category, followed by a section for
+/// each group.
+fn listing_sections(prefix: &str, registry: &CommandRegistry) -> Vec<Section> {
+    let mut categories: Vec<Section> = Vec::new();
+    let mut groups = Vec::new();
+
+    for entry in registry.entries() {
+        match entry {
+            &Entry::Command(ref cmd) => {
+                let line = entry_line(prefix, "", entry);
+                match categories.iter().position(|s| s.title == cmd.category()) {
+                    Some(idx) => categories[idx].lines.push(line),
+                    None => {
+                        categories.push(Section {
+                                            title: cmd.category().to_string(),
+                                            lines: vec![line],
+                                        })
+                    }
+                }
+            }
+            &Entry::Group(ref group) => {
+                let mut lines = Vec::new();
+                if let Some(description) = group.description() {
+                    lines.push(format!("*{}*", description));
+                }
+                lines.extend(group.commands()
+                                 .entries()
+                                 .map(|e| entry_line(prefix, group.name(), e)));
+                groups.push(Section {
+                                title: format!("{}{}", prefix, group.name()),
+                                lines: lines,
+                            });
+            }
+        }
+    }
+
+    categories.extend(groups);
+    categories
+}
+
+fn command_sections(prefix: &str, path: &str, command: &Command) -> Vec<Section> {
+    let mut sections = Vec::new();
+
+    let usage = match command::usage(command) {
+        Some(u) => format!("`{}{} {}`", prefix, path, u),
+        None => format!("`{}{}`", prefix, path),
+    };
+    sections.push(Section {
+                      title: "Usage".to_string(),
+                      lines: vec![usage],
+                  });
+
+    if !command.arguments().is_empty() {
+        sections.push(Section {
+                          title: "Arguments".to_string(),
+                          lines: command.arguments().iter().map(argument_line).collect(),
+                      });
+    }
+
+    if !command.aliases().is_empty() {
+        // Aliases replace the last word of the path.
+        let parent = match path.rfind(' ') {
+            Some(idx) => &path[..idx + 1],
+            None => "",
+        };
+        let aliases: Vec<String> = command.aliases()
+            .iter()
+            .map(|a| format!("`{}{}{}`", prefix, parent, a))
+            .collect();
+        sections.push(Section {
+                          title: "Aliases".to_string(),
+                          lines: vec![aliases.join(", ")],
+                      });
+    }
+
+    if !command.examples().is_empty() {
+        sections.push(Section {
+                          title: "Examples".to_string(),
+                          lines: command.examples()
+                              .iter()
+                              .map(|e| format!("`{}{}`", prefix, e))
+                              .collect(),
+                      });
+    }
+
+    if !command.permissions().is_empty() {
+        sections.push(Section {
+                          title: "Requires".to_string(),
+                          lines: command.permissions().iter().map(|p| p.to_string()).collect(),
+                      });
+    }
+
+    if let Some(cooldown) = command.cooldown() {
+        sections.push(Section {
+                          title: "Cooldown".to_string(),
+                          lines: vec![cooldown.to_string()],
+                      });
+    }
+
+    sections
+}
+
+fn group_sections(prefix: &str, path: &str, group: &CommandGroup) -> Vec<Section> {
+    let mut sections = vec![Section {
+                                title: "Subcommands".to_string(),
+                                lines: group.commands()
+                                    .entries()
+                                    .map(|e| entry_line(prefix, path, e))
+                                    .collect(),
+                            }];
+
+    if let Some(default) = group.default_command() {
+        sections.push(Section {
+                          title: "Default".to_string(),
+                          lines: vec![format!("`{}{}` runs `{}{} {}`.",
+                                              prefix,
+                                              path,
+                                              prefix,
+                                              path,
+                                              default.name())],
+                      });
+    }
+
+    sections
+}
+
+fn entry_line(prefix: &str, parent_path: &str, entry: &Entry) -> String {
+    let path = if parent_path.is_empty() {
+        entry.name().to_string()
+    } else {
+        format!("{} {}", parent_path, entry.name())
+    };
+
+    match entry.description() {
+        Some(d) => format!("`{}{}` - {}", prefix, path, d),
+        None => format!("`{}{}`", prefix, path),
+    }
+}
+
+fn argument_line(argument: &Argument) -> String {
+    let name = match (argument.is_named(), argument.short()) {
+        (true, Some(short)) => format!("`--{}`, `-{}`", argument.name, short),
+        (true, None) => format!("`--{}`", argument.name),
+        (false, _) => format!("`{}`", argument.name),
+    };
+    match argument.arity {
+        Arity::Required => format!("{} - {}", name, argument.description),
+        Arity::Optional(Some(default)) |
+        Arity::Named { default: Some(default), .. } => {
+            format!("{} - {} (optional, defaults to `{}`)",
+                    name,
+                    argument.description,
+                    default)
+        }
+        _ => format!("{} - {} (optional)", name, argument.description),
+    }
+}
+
+/// Split `sections` into pages of embed fields that fit Discord's limits.
+fn paginate(sections: Vec<Section>) -> Vec<Page> {
+    let mut fields = Vec::new();
+    for section in sections {
+        let mut value = String::new();
+        let mut continued = false;
+        for line in section.lines {
+            let line: String = line.chars().take(MAX_FIELD_LENGTH).collect();
+            if !value.is_empty() &&
+               value.chars().count() + 1 + line.chars().count() > MAX_FIELD_LENGTH {
+                fields.push((field_title(&section.title, continued), value));
+                value = String::new();
+                continued = true;
+            }
+            if !value.is_empty() {
+                value.push('\n');
+            }
+            value.push_str(&line);
+        }
+        if !value.is_empty() {
+            fields.push((field_title(&section.title, continued), value));
+        }
+    }
+
+    let mut pages = Vec::new();
+    let mut page: Page = Vec::new();
+    let mut page_length = 0;
+    for (title, value) in fields {
+        let field_length = title.chars().count() + value.chars().count();
+        if !page.is_empty() &&
+           (page.len() == MAX_FIELDS_PER_PAGE || page_length + field_length > MAX_PAGE_LENGTH) {
+            pages.push(page);
+            page = Vec::new();
+            page_length = 0;
+        }
+        page_length += field_length;
+        page.push((title, value));
+    }
+    if !page.is_empty() || pages.is_empty() {
+        pages.push(page);
+    }
+
+    pages
+}
+
+fn field_title(title: &str, continued: bool) -> String {
+    if continued {
+        format!("{} (continued)", title)
+    } else {
+        title.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(title: &str, lines: usize, line_length: usize) -> Section {
+        Section {
+            title: title.to_string(),
+            lines: (0..lines).map(|_| "x".repeat(line_length)).collect(),
+        }
+    }
+
+    #[test]
+    fn paginate_small_listing() {
+        let pages = paginate(vec![section("General", 3, 10), section("!ffxiv", 2, 10)]);
+
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].len(), 2);
+        assert_eq!(pages[0][0].0, "General");
+        assert_eq!(pages[0][0].1.lines().count(), 3);
+    }
+
+    #[test]
+    fn paginate_nothing() {
+        let pages = paginate(vec![]);
+
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].is_empty());
+    }
+
+    #[test]
+    fn paginate_splits_long_sections() {
+        let pages = paginate(vec![section("General", 30, 100)]);
+        let fields = &pages[0];
+
+        assert_eq!(fields[0].0, "General");
+        assert_eq!(fields[1].0, "General (continued)");
+        for &(_, ref value) in fields {
+            assert!(value.chars().count() <= MAX_FIELD_LENGTH);
+        }
+        let total_lines: usize = fields.iter().map(|f| f.1.lines().count()).sum();
+        assert_eq!(total_lines, 30);
+    }
+
+    #[test]
+    fn paginate_splits_pages_by_field_count() {
+        let sections = (0..30).map(|i| section(&format!("Section {}", i), 1, 10)).collect();
+        let pages = paginate(sections);
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].len(), MAX_FIELDS_PER_PAGE);
+        assert_eq!(pages[1].len(), 5);
+    }
+
+    #[test]
+    fn paginate_splits_pages_by_length() {
+        let sections = (0..8).map(|i| section(&format!("Section {}", i), 1, 1000)).collect();
+        let pages = paginate(sections);
+
+        assert_eq!(pages.len(), 2);
+        for page in &pages {
+            let length: usize = page.iter().map(|f| f.0.len() + f.1.len()).sum();
+            assert!(length <= MAX_PAGE_LENGTH);
+        }
+    }
+}
@@ -1,15 +1,70 @@
-use discord::Discord;
-use discord::model::Message;
 use rand;
 use rand::Rng;
 use regex::Regex;
-use framework::args::Args;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use framework::args::{ArgError, Args, FromArgs};
+use framework::command::{Argument, Arity, Command, CommandError, CommandResult, Context};
+use framework::cooldown::{Bucket, Cooldown};
+use framework::registry::CommandRegistry;
 
-pub fn ping(bot: &Discord, msg: &Message, _args: Args) {
-    let _ = bot.send_message(msg.channel_id, "Pong!", "", false);
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(Ping);
+    registry.register(EightBall);
+    registry.register(Roll);
 }
 
-pub fn eight_ball(bot: &Discord, msg: &Message, _args: Args) {
+pub struct Ping;
+
+impl Command for Ping {
+    fn name(&self) -> &'static str {
+        "ping"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Check that the bot is listening.")
+    }
+
+    fn exec(&self, ctx: &Context, _args: Args) -> CommandResult {
+        ctx.send_message("Pong!")?;
+        Ok(())
+    }
+}
+
+pub struct EightBall;
+
+impl Command for EightBall {
+    fn name(&self) -> &'static str {
+        "8-ball"
+    }
+
+    fn category(&self) -> &'static str {
+        "Fun"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Ask the magic 8-ball any yes/no question.")
+    }
+
+    fn arguments(&self) -> &'static [Argument] {
+        &[Argument {
+              name: "question",
+              description: "What to ask. The 8-ball knows anyway.",
+              arity: Arity::Rest,
+          }]
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        &["8-ball Will it rain tomorrow?"]
+    }
+
+    fn exec(&self, ctx: &Context, _args: Args) -> CommandResult {
+        eight_ball(ctx)
+    }
+}
+
+fn eight_ball(ctx: &Context) -> CommandResult {
     let responses = ["It is certain",
                      "It is decidedly so",
                      "Without a doubt",
@@ -31,50 +86,126 @@ pub fn eight_ball(bot: &Discord, msg: &Message, _args: Args) {
                      "Outlook not so good",
                      "Very doubtful"];
 
-    let _ = bot.send_message(msg.channel_id,
-                             rand::thread_rng().choose(&responses).unwrap(),
-                             "",
-                             false);
+    ctx.send_message(rand::thread_rng().choose(&responses).unwrap())?;
+    Ok(())
 }
 
-pub fn roll(bot: &Discord, msg: &Message, mut args: Args) {
-    let roll = match args.single::<String>() {
-        Ok(r) => r,
-        Err(_) => "1d1000".to_string(),
-    };
-
-    let re = Regex::new("^(?P<quantity>\\d+)?d(?P<sides>\\d+)$").expect("Couldn't create regex");
-    let caps = match re.captures(&roll) {
-        Some(c) => c,
-        None => return,
-    };
-
-    let quantity = match caps.name("quantity") {
-        Some(m) => {
-            m.as_str()
-                .parse::<u64>()
-                .expect("Couldn't parse quantity of dice")
-        }
-        None => 1,
-    };
-
-    let sides = caps.name("sides")
-        .unwrap()
-        .as_str()
-        .parse::<u64>()
-        .expect("Couldn't parse how many sides per die");
-
-    let mut total = 0;
-
-    for _ in 0..quantity {
-        total += rand::thread_rng().gen_range(0, sides) + 1;
-    }
-
-    let _ = bot.send_message(msg.channel_id,
-                             &format!("{}: Rolled {} and got {}",
-                                     msg.author.mention(),
-                                     roll,
-                                     total),
-                             "",
-                             false);
+/// Upper bound on dice per roll, so one command can't keep the bot busy adding.
+const MAX_DICE: u64 = 1000;
+
+pub struct Roll;
+
+impl Command for Roll {
+    fn name(&self) -> &'static str {
+        "roll"
+    }
+
+    fn category(&self) -> &'static str {
+        "Fun"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Roll some dice and add them up.")
+    }
+
+    fn arguments(&self) -> &'static [Argument] {
+        &[Argument {
+              name: "dice",
+              description: "How many dice with how many sides, like `2d6`.",
+              arity: Arity::Optional(Some("1d1000")),
+          }]
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        &["roll", "roll 2d6", "roll d20"]
+    }
+
+    fn cooldown(&self) -> Option<Cooldown> {
+        Some(Cooldown {
+                 bucket: Bucket::User,
+                 uses: 5,
+                 per: Duration::from_secs(10),
+             })
+    }
+
+    fn exec(&self, ctx: &Context, args: Args) -> CommandResult {
+        roll(ctx, args.parse()?)
+    }
+}
+
+struct RollArgs {
+    dice: Dice,
+}
+
+impl FromArgs for RollArgs {
+    fn from_args(args: &Args) -> Result<RollArgs, ArgError> {
+        Ok(RollArgs { dice: args.value("dice")? })
+    }
+}
+
+/// Some number of dice with the same number of sides, like `2d6`.
+struct Dice {
+    quantity: u64,
+    sides: u64,
+    /// As the user wrote it.
+    text: String,
+}
+
+impl FromStr for Dice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Dice, String> {
+        let re = Regex::new("^(?P<quantity>\\d+)?d(?P<sides>\\d+)$")
+            .expect("Couldn't create regex");
+        let caps = match re.captures(s) {
+            Some(c) => c,
+            None => return Err(format!("`{}` isn't a dice roll, try something like `2d6`.", s)),
+        };
+
+        let quantity = match caps.name("quantity") {
+            Some(m) => {
+                match m.as_str().parse::<u64>() {
+                    Ok(q) if q <= MAX_DICE => q,
+                    _ => return Err(format!("I can only roll up to {} dice at once.", MAX_DICE)),
+                }
+            }
+            None => 1,
+        };
+
+        let sides = match caps.name("sides").unwrap().as_str().parse::<u64>() {
+            Ok(s) if s > 0 => s,
+            Ok(_) => return Err("Dice need at least one side.".to_string()),
+            Err(_) => return Err("Those dice have too many sides.".to_string()),
+        };
+
+        Ok(Dice {
+               quantity: quantity,
+               sides: sides,
+               text: s.to_string(),
+           })
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+fn roll(ctx: &Context, args: RollArgs) -> CommandResult {
+    let dice = args.dice;
+    let mut total: u64 = 0;
+
+    for _ in 0..dice.quantity {
+        total = match total.checked_add(rand::thread_rng().gen_range(0, dice.sides) + 1) {
+            Some(t) => t,
+            None => return Err(CommandError::BadArguments("That roll is too big to add up.".to_string())),
+        };
+    }
+
+    ctx.send_message(&format!("{}: Rolled {} and got {}",
+                             ctx.message.author.id.mention(),
+                             dice,
+                             total))?;
+    Ok(())
 }
@@ -1,2 +1,32 @@
+use config::Config;
+use framework::registry::CommandRegistry;
+
+pub mod admin;
 pub mod ffxiv;
+pub mod help;
 pub mod misc;
+pub mod prefix;
+
+/// Every command module, by the name used to enable it in the config.
+pub const MODULES: &'static [&'static str] = &["help", "admin", "misc", "ffxiv", "prefix"];
+
+/// Register the commands from each module `config` enables.
+pub fn register_all(registry: &mut CommandRegistry, config: &Config) {
+    let enabled = |module: &str| config.commands.enabled.iter().any(|m| m == module);
+
+    if enabled("help") {
+        help::register(registry);
+    }
+    if enabled("admin") {
+        admin::register(registry);
+    }
+    if enabled("misc") {
+        misc::register(registry);
+    }
+    if enabled("ffxiv") {
+        ffxiv::register(registry, &config.ffxiv);
+    }
+    if enabled("prefix") {
+        prefix::register(registry);
+    }
+}
@@ -0,0 +1,108 @@
+use discord::model::ServerId;
+use framework::args::Args;
+use framework::command::{Argument, Arity, Command, CommandError, CommandResult, Context};
+use framework::group::CommandGroup;
+use framework::permissions::Permission;
+use framework::prefixes;
+use framework::registry::CommandRegistry;
+
+pub fn register(registry: &mut CommandRegistry) {
+    let mut prefix = CommandGroup::new("prefix", "Show or change the command prefix for this server");
+    prefix.register(Show);
+    prefix.register(Set);
+    prefix.register(Reset);
+    prefix.set_default("show");
+    registry.register_group(prefix);
+}
+
+pub struct Show;
+
+impl Command for Show {
+    fn name(&self) -> &'static str {
+        "show"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Show the command prefix used here.")
+    }
+
+    fn exec(&self, ctx: &Context, _args: Args) -> CommandResult {
+        ctx.send_message(&format!("The command prefix here is `{}`. Mentioning me works too: {} help",
+                                 ctx.prefix,
+                                 ctx.responder.user_id().mention()))?;
+        Ok(())
+    }
+}
+
+pub struct Set;
+
+impl Command for Set {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Change the command prefix for this server.")
+    }
+
+    fn arguments(&self) -> &'static [Argument] {
+        &[Argument {
+              name: "prefix",
+              description: "The new prefix. It can't contain spaces.",
+              arity: Arity::Required,
+          }]
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        &["prefix set ?", "prefix set tb!"]
+    }
+
+    fn permissions(&self) -> &'static [Permission] {
+        &[Permission::ServerAdmin]
+    }
+
+    fn exec(&self, ctx: &Context, args: Args) -> CommandResult {
+        let server_id = require_server(ctx)?;
+        let new_prefix: String = args.value("prefix")?;
+        prefixes::validate(&new_prefix).map_err(CommandError::BadArguments)?;
+
+        ctx.dispatcher
+            .prefixes()
+            .set(server_id, &new_prefix)
+            .map_err(|e| CommandError::Internal(format!("Unable to save prefix: {}", e)))?;
+        ctx.send_message(&format!("The command prefix is now `{}`.", new_prefix))?;
+        Ok(())
+    }
+}
+
+pub struct Reset;
+
+impl Command for Reset {
+    fn name(&self) -> &'static str {
+        "reset"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Go back to the default command prefix.")
+    }
+
+    fn permissions(&self) -> &'static [Permission] {
+        &[Permission::ServerAdmin]
+    }
+
+    fn exec(&self, ctx: &Context, _args: Args) -> CommandResult {
+        let server_id = require_server(ctx)?;
+        ctx.dispatcher
+            .prefixes()
+            .reset(server_id)
+            .map_err(|e| CommandError::Internal(format!("Unable to save prefix: {}", e)))?;
+        ctx.send_message(&format!("The command prefix is now `{}`.",
+                                 ctx.dispatcher.prefixes().default_prefix()))?;
+        Ok(())
+    }
+}
+
+fn require_server(ctx: &Context) -> Result<ServerId, CommandError> {
+    ctx.message.server_id
+        .ok_or(CommandError::BadArguments("Prefixes can only be changed in a server.".to_string()))
+}
@@ -0,0 +1,423 @@
+use commands;
+use framework::permissions;
+use framework::prefixes;
+use logging::{self, Format};
+use std::env;
+use std::error::Error as StdError;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use toml;
+
+/// Where the config file is looked for when `TECHNOBOT_CONFIG` isn't set.
+pub const DEFAULT_PATH: &'static str = "technobot.toml";
+
+/// Everything the bot can be configured with. Read from a TOML file, with
+/// environment variables taking precedence.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The Discord bot token. Only needed to connect to Discord.
+    pub token: Option<String>,
+    /// Discord user IDs allowed to run owner-only commands.
+    pub owners: Vec<u64>,
+    /// The command prefix for servers that haven't chosen their own.
+    pub prefix: String,
+    /// Where bot state is saved.
+    pub storage_file: PathBuf,
+    /// How many gateway shards to connect, or `None` for Discord's recommendation.
+    pub shards: Option<u8>,
+    /// How many commands can run at once.
+    pub workers: usize,
+    /// The log level, optionally with levels for particular modules, like
+    /// `info,technobot::gateway=debug`. See `logging::Filter`.
+    pub log_level: String,
+    /// Whether log lines are written for people or as JSON.
+    pub log_format: Format,
+    /// Where to serve `/metrics` and `/healthz`, like `127.0.0.1:9102`. Not
+    /// served when `None`.
+    pub http_listen: Option<String>,
+    pub commands: CommandsConfig,
+    pub ffxiv: FfxivConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandsConfig {
+    /// The command modules to register. See `commands::MODULES`.
+    pub enabled: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FfxivConfig {
+    /// The JSON feed of FF XIV events.
+    pub timers_url: String,
+    /// How many seconds to reuse a fetched copy of the timers feed for.
+    pub timers_cache_ttl: u64,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            token: None,
+            owners: Vec::new(),
+            prefix: "!".to_string(),
+            storage_file: PathBuf::from("technobot-data.json"),
+            shards: None,
+            workers: 4,
+            log_level: "info".to_string(),
+            log_format: Format::Human,
+            http_listen: None,
+            commands: CommandsConfig::default(),
+            ffxiv: FfxivConfig::default(),
+        }
+    }
+}
+
+impl Default for CommandsConfig {
+    fn default() -> CommandsConfig {
+        CommandsConfig { enabled: commands::MODULES.iter().map(|m| m.to_string()).collect() }
+    }
+}
+
+impl Default for FfxivConfig {
+    fn default() -> FfxivConfig {
+        FfxivConfig {
+            timers_url: "http://www.xenoveritas.org/static/ffxiv/timers.json".to_string(),
+            timers_cache_ttl: 300,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file exists but couldn't be read.
+    Io(PathBuf, io::Error),
+    /// The config file isn't valid TOML, or has settings we don't know.
+    Parse(PathBuf, toml::de::Error),
+    /// Settings that were understood but can't be used, one message each.
+    Invalid(Vec<String>),
+}
+
+impl StdError for ConfigError {
+    fn description(&self) -> &str {
+        match self {
+            &ConfigError::Io(..) => "Unable to read config file",
+            &ConfigError::Parse(..) => "Unable to parse config file",
+            &ConfigError::Invalid(_) => "Invalid configuration",
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ConfigError::Io(ref path, ref e) => write!(f, "Unable to read {}: {}", path.display(), e),
+            &ConfigError::Parse(ref path, ref e) => {
+                write!(f, "Unable to parse {}: {}", path.display(), e)
+            }
+            &ConfigError::Invalid(ref problems) => {
+                write!(f, "Invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Read the file named by `TECHNOBOT_CONFIG` (or `technobot.toml`), then
+    /// apply environment variable overrides. A missing file means defaults.
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = env::var("TECHNOBOT_CONFIG").unwrap_or(DEFAULT_PATH.to_string());
+        let mut config = Config::from_file(Path::new(&path))?;
+        config.apply_env(|name| env::var(name).ok())?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let mut contents = String::new();
+        match File::open(path) {
+            Ok(mut f) => {
+                f.read_to_string(&mut contents)
+                    .map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+            }
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(ConfigError::Io(path.to_path_buf(), e)),
+        }
+
+        Config::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    /// Override settings with the environment variables `var` finds. Empty
+    /// variables are ignored, so `.env` files can leave them blank.
+    pub fn apply_env<F>(&mut self, var: F) -> Result<(), ConfigError>
+        where F: Fn(&str) -> Option<String>
+    {
+        let var = |name: &str| var(name).and_then(|v| if v.is_empty() { None } else { Some(v) });
+        let mut problems = Vec::new();
+
+        if let Some(token) = var("DISCORD_TOKEN") {
+            self.token = Some(token);
+        }
+        if let Some(owners) = var("TECHNOBOT_OWNERS") {
+            match permissions::parse_owners(&owners) {
+                Ok(o) => self.owners = o.iter().map(|id| id.0).collect(),
+                Err(e) => problems.push(format!("TECHNOBOT_OWNERS: {}", e)),
+            }
+        }
+        if let Some(prefix) = var("TECHNOBOT_PREFIX") {
+            self.prefix = prefix;
+        }
+        if let Some(file) = var("TECHNOBOT_STORAGE_FILE") {
+            self.storage_file = PathBuf::from(file);
+        }
+        if let Some(shards) = var("TECHNOBOT_SHARDS") {
+            match shards.parse() {
+                Ok(n) => self.shards = Some(n),
+                Err(_) => {
+                    problems.push(format!("TECHNOBOT_SHARDS must be a number from 1 to 255, not {:?}.",
+                                          shards))
+                }
+            }
+        }
+        if let Some(workers) = var("TECHNOBOT_WORKERS") {
+            match workers.parse() {
+                Ok(n) => self.workers = n,
+                Err(_) => {
+                    problems.push(format!("TECHNOBOT_WORKERS must be a number, not {:?}.", workers))
+                }
+            }
+        }
+        if let Some(level) = var("TECHNOBOT_LOG_LEVEL") {
+            self.log_level = level;
+        }
+        if let Some(format) = var("TECHNOBOT_LOG_FORMAT") {
+            match format.parse() {
+                Ok(f) => self.log_format = f,
+                Err(e) => problems.push(format!("TECHNOBOT_LOG_FORMAT {}", e)),
+            }
+        }
+        if let Some(address) = var("TECHNOBOT_HTTP_LISTEN") {
+            self.http_listen = Some(address);
+        }
+        if let Some(modules) = var("TECHNOBOT_COMMANDS") {
+            self.commands.enabled = modules.split(',')
+                .map(|m| m.trim().to_string())
+                .filter(|m| !m.is_empty())
+                .collect();
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    /// Check the settings make sense together. `needs_token` is false when
+    /// running without Discord.
+    pub fn validate(&self, needs_token: bool) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if needs_token && self.token.as_ref().map_or(true, |t| t.trim().is_empty()) {
+            problems.push("No Discord token. Set `token` in the config file, or DISCORD_TOKEN."
+                              .to_string());
+        }
+        if self.owners.contains(&0) {
+            problems.push("`owners` must be Discord user IDs, and 0 isn't one.".to_string());
+        }
+        if let Err(e) = prefixes::validate(&self.prefix) {
+            problems.push(format!("`prefix` {:?} can't be used: {}", self.prefix, e));
+        }
+        if self.shards == Some(0) {
+            problems.push("`shards` must be at least 1. Leave it out to use Discord's recommendation."
+                              .to_string());
+        }
+        if self.workers == 0 {
+            problems.push("`workers` must be at least 1.".to_string());
+        }
+        if let Err(e) = self.log_filter() {
+            problems.push(format!("`log_level` {:?} can't be used: {}", self.log_level, e));
+        }
+        if let Some(ref address) = self.http_listen {
+            if address.parse::<SocketAddr>().is_err() {
+                problems.push(format!("`http_listen` must be an IP address and port, like \
+                                       127.0.0.1:9102, not {:?}.",
+                                      address));
+            }
+        }
+        for module in &self.commands.enabled {
+            if !commands::MODULES.contains(&module.as_str()) {
+                problems.push(format!("Unknown command module {:?} in `commands.enabled`. Known \
+                                       modules are {}.",
+                                      module,
+                                      commands::MODULES.join(", ")));
+            }
+        }
+        if !self.ffxiv.timers_url.starts_with("http://") &&
+           !self.ffxiv.timers_url.starts_with("https://") {
+            problems.push(format!("`ffxiv.timers_url` must be an http or https URL, not {:?}.",
+                                  self.ffxiv.timers_url));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    /// Which log lines to write, from `log_level`.
+    pub fn log_filter(&self) -> Result<logging::Filter, String> {
+        logging::Filter::parse(&self.log_level)
+    }
+}
+
+impl FromStr for Config {
+    type Err = toml::de::Error;
+
+    fn from_str(contents: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(contents)
+    }
+}
+
+impl FfxivConfig {
+    pub fn timers_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.timers_cache_ttl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn problems(config: &Config, needs_token: bool) -> Vec<String> {
+        match config.validate(needs_token) {
+            Ok(()) => vec![],
+            Err(ConfigError::Invalid(problems)) => problems,
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn defaults_are_valid_without_token() {
+        let config = Config::default();
+
+        assert!(problems(&config, false).is_empty());
+        assert_eq!(problems(&config, true).len(), 1);
+    }
+
+    #[test]
+    fn parse_full_file() {
+        let config = Config::from_str(r#"
+            token = "abc"
+            owners = [123, 456]
+            prefix = "?"
+            storage_file = "/var/lib/technobot/data.json"
+            shards = 2
+            workers = 8
+            log_level = "debug,technobot::gateway=trace"
+            log_format = "json"
+            http_listen = "0.0.0.0:9102"
+
+            [commands]
+            enabled = ["help", "ffxiv"]
+
+            [ffxiv]
+            timers_url = "https://example.com/timers.json"
+            timers_cache_ttl = 60
+        "#)
+            .unwrap();
+
+        assert_eq!(config.token, Some("abc".to_string()));
+        assert_eq!(config.owners, vec![123, 456]);
+        assert_eq!(config.prefix, "?");
+        assert_eq!(config.storage_file, PathBuf::from("/var/lib/technobot/data.json"));
+        assert_eq!(config.shards, Some(2));
+        assert_eq!(config.workers, 8);
+        assert_eq!(config.log_level, "debug,technobot::gateway=trace");
+        assert_eq!(config.log_format, Format::Json);
+        assert_eq!(config.http_listen, Some("0.0.0.0:9102".to_string()));
+        assert_eq!(config.commands.enabled, vec!["help", "ffxiv"]);
+        assert_eq!(config.ffxiv.timers_url, "https://example.com/timers.json");
+        assert_eq!(config.ffxiv.timers_cache_ttl(), Duration::from_secs(60));
+        assert!(problems(&config, true).is_empty());
+    }
+
+    #[test]
+    fn partial_file_keeps_defaults() {
+        let config = Config::from_str("prefix = \"?\"\n[ffxiv]\ntimers_cache_ttl = 10\n").unwrap();
+
+        assert_eq!(config.prefix, "?");
+        assert_eq!(config.workers, 4);
+        assert_eq!(config.ffxiv.timers_url, FfxivConfig::default().timers_url);
+    }
+
+    #[test]
+    fn unknown_settings_are_errors() {
+        assert!(Config::from_str("prefx = \"?\"").is_err());
+        assert!(Config::from_str("[ffxiv]\ntimers = \"x\"").is_err());
+    }
+
+    #[test]
+    fn missing_file_is_defaults() {
+        let config = Config::from_file(Path::new("/nonexistent/technobot.toml")).unwrap();
+
+        assert_eq!(config.prefix, "!");
+    }
+
+    #[test]
+    fn env_overrides_file() {
+        let mut config = Config::from_str("prefix = \"?\"\nworkers = 2").unwrap();
+        let mut vars = HashMap::new();
+        vars.insert("TECHNOBOT_PREFIX", "tb!");
+        vars.insert("TECHNOBOT_OWNERS", "1, 2");
+        vars.insert("TECHNOBOT_SHARDS", "");
+        vars.insert("TECHNOBOT_COMMANDS", "help, misc");
+
+        config.apply_env(|name| vars.get(name).map(|v| v.to_string())).unwrap();
+
+        assert_eq!(config.prefix, "tb!");
+        assert_eq!(config.owners, vec![1, 2]);
+        assert_eq!(config.shards, None);
+        assert_eq!(config.workers, 2);
+        assert_eq!(config.commands.enabled, vec!["help", "misc"]);
+    }
+
+    #[test]
+    fn bad_env_values_are_reported() {
+        let mut config = Config::default();
+        let mut vars = HashMap::new();
+        vars.insert("TECHNOBOT_SHARDS", "lots");
+        vars.insert("TECHNOBOT_WORKERS", "-1");
+        vars.insert("TECHNOBOT_LOG_FORMAT", "xml");
+
+        match config.apply_env(|name| vars.get(name).map(|v| v.to_string())) {
+            Err(ConfigError::Invalid(problems)) => assert_eq!(problems.len(), 3),
+            other => panic!("expected invalid config, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let mut config = Config::default();
+        config.prefix = "a b".to_string();
+        config.shards = Some(0);
+        config.workers = 0;
+        config.log_level = "loud".to_string();
+        config.http_listen = Some("localhost".to_string());
+        config.commands.enabled.push("music".to_string());
+        config.ffxiv.timers_url = "ftp://example.com".to_string();
+
+        assert_eq!(problems(&config, true).len(), 8);
+    }
+}
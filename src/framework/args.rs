@@ -1,14 +1,17 @@
-use nom::{self, ErrorKind, IResult, Needed};
+use framework::command::{Argument, Arity};
+use framework::entities::{self, Directory, Entity};
 use std::fmt;
-use std::str::{self, FromStr};
+use std::str::FromStr;
 use std::error::Error as StdError;
-use util::arg_parser;
+use util::arg_parser::{self, Named, ParseError, Reason};
 
 #[derive(Debug)]
 pub enum Error<E: StdError> {
     Parse(E),
     Empty,
-    ArgParse,
+    /// The arguments couldn't be split into values. The position is a byte
+    /// offset into the arguments as typed.
+    ArgParse(ParseError),
 }
 
 impl<E: StdError> From<E> for Error<E> {
@@ -20,7 +23,7 @@ impl<E: StdError> From<E> for Error<E> {
 impl<E: StdError> StdError for Error<E> {
     fn description(&self) -> &str {
         match self {
-            &Error::ArgParse => "Error parsing argument string",
+            &Error::ArgParse(_) => "Error parsing argument string",
             &Error::Parse(ref e) => e.description(),
             &Error::Empty => "No remaining unparsed arguments",
         }
@@ -37,7 +40,9 @@ impl<E: StdError> StdError for Error<E> {
 impl<E: StdError> fmt::Display for Error<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            &Error::ArgParse => write!(f, "Error parsing argument string"),
+            &Error::ArgParse(ref e) => {
+                write!(f, "Error parsing argument string at {}: {}", e.position, e.reason)
+            }
             &Error::Parse(ref e) => write!(f, "Parse error: {}", e),
             &Error::Empty => write!(f, "No remaining unparsed arguments"),
         }
@@ -46,49 +51,747 @@ impl<E: StdError> fmt::Display for Error<E> {
 
 type Result<T, E> = ::std::result::Result<T, Error<E>>;
 
+/// How many characters of a long line of arguments to show around a problem.
+const EXCERPT_WIDTH: usize = 60;
+
+/// Why what the user typed doesn't fit a command's arguments, and where.
+#[derive(Debug, PartialEq)]
+pub struct ArgError {
+    pub problem: Problem,
+    /// The arguments as typed.
+    pub input: String,
+    /// Where in `input` the problem is, as a byte offset.
+    pub position: usize,
+}
+
+/// What's wrong with the arguments. Where one argument is at fault it's
+/// named, so the user knows what to fix.
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    /// A required argument was left out.
+    Missing(&'static str),
+    /// A value couldn't be parsed as the argument's type.
+    Invalid {
+        argument: &'static str,
+        value: String,
+        reason: String,
+    },
+    /// There were more values than the command takes, starting with this one.
+    Unexpected(String),
+    /// The arguments couldn't be split into values, like when a quote isn't
+    /// closed.
+    Syntax(Reason),
+}
+
+impl ArgError {
+    fn new(problem: Problem, input: &str, position: usize) -> ArgError {
+        ArgError {
+            problem: problem,
+            input: input.to_string(),
+            position: position,
+        }
+    }
+
+    /// A problem at the end of what was typed, like a missing argument.
+    fn at_end(problem: Problem, input: &str) -> ArgError {
+        ArgError::new(problem, input, input.trim_right().len())
+    }
+
+    /// The line of the arguments with the problem, and a caret under where it
+    /// is, as a code block so they line up. `None` when nothing was typed,
+    /// since there's nothing to point at.
+    pub fn excerpt(&self) -> Option<String> {
+        let input = &self.input;
+        if input.trim().is_empty() {
+            return None;
+        }
+
+        let position = self.position.min(input.len());
+        let line_start = input[..position].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[position..].find('\n').map_or(input.len(), |i| position + i);
+        let line: Vec<char> = input[line_start..line_end]
+            .chars()
+            .map(|c| if c == '\t' { ' ' } else { c })
+            .collect();
+        let column = input[line_start..position].chars().count();
+
+        // Long lines are cut down to the part around the problem.
+        let start = if line.len() > EXCERPT_WIDTH {
+            column.saturating_sub(EXCERPT_WIDTH / 2).min(line.len() - EXCERPT_WIDTH)
+        } else {
+            0
+        };
+        let end = (start + EXCERPT_WIDTH).min(line.len());
+        let mut shown = String::new();
+        let mut caret = column - start;
+        if start > 0 {
+            shown.push('…');
+            caret += 1;
+        }
+        shown.extend(&line[start..end]);
+        if end < line.len() {
+            shown.push('…');
+        }
+
+        Some(format!("```\n{}\n{}^\n```", shown, " ".repeat(caret)))
+    }
+}
+
+impl StdError for ArgError {
+    fn description(&self) -> &str {
+        match self.problem {
+            Problem::Missing(_) => "Missing argument",
+            Problem::Invalid { .. } => "Invalid argument",
+            Problem::Unexpected(_) => "Unexpected argument",
+            Problem::Syntax(_) => "Malformed arguments",
+        }
+    }
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.problem {
+            Problem::Missing(argument) => write!(f, "Missing `{}`.", argument),
+            Problem::Invalid { argument, ref reason, .. } => {
+                write!(f, "Invalid `{}`: {}", argument, reason)
+            }
+            Problem::Unexpected(ref value) => write!(f, "Unexpected `{}`.", value),
+            Problem::Syntax(ref reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// A command's arguments as a struct, built from the values matched to the
+/// command's `arguments`.
+pub trait FromArgs: Sized {
+    fn from_args(args: &Args) -> ::std::result::Result<Self, ArgError>;
+}
+
 #[derive(Debug)]
 pub struct Args {
+    /// The arguments as typed.
+    input: String,
     unparsed: String,
+    /// The values matched to each of the command's arguments, by name.
+    values: Vec<(&'static str, Vec<Value>)>,
+}
+
+/// A value, and where in the arguments it was typed.
+#[derive(Debug)]
+struct Value {
+    text: String,
+    position: usize,
 }
 
 impl Args {
     pub fn new(arg_str: &str) -> Args {
-        Args { unparsed: arg_str.to_string() }
+        Args {
+            input: arg_str.to_string(),
+            unparsed: arg_str.to_string(),
+            values: Vec::new(),
+        }
     }
 
-    pub fn single<T: FromStr>(&mut self) -> Result<T, T::Err>
-        where T::Err: StdError
-    {
-        if self.unparsed.is_empty() {
-            return Err(Error::Empty);
+    /// Match `arg_str` to the arguments in `schema`. Values are separated by
+    /// spaces, and can be quoted to include spaces. Positional arguments take
+    /// values in order, and named ones can be given anywhere before a rest
+    /// argument starts, as `--name value`, `--name=value` or `-n value` (or
+    /// just the name for flags). `--` ends the named arguments, so later
+    /// values can start with a dash.
+    ///
+    /// Commands that declare no arguments aren't checked, and can make what
+    /// they like of `rest`.
+    pub fn with_schema(arg_str: &str,
+                       schema: &[Argument])
+                       -> ::std::result::Result<Args, ArgError> {
+        let mut args = Args::new(arg_str);
+        if schema.is_empty() {
+            return Ok(args);
         }
 
-        let to_parse = self.unparsed.clone();
-        match arg_parser::single_arg(to_parse.as_bytes()) {
-            IResult::Done(rest, arg_str) => {
-                let result = arg_str.parse::<T>()?;
-                self.unparsed = str::from_utf8(rest).unwrap().to_string();
-                Ok(result)
+        args.values = schema.iter().map(|a| (a.name, Vec::new())).collect();
+        // Only commands with named arguments look for them, so the rest can
+        // take values like `-` without quoting.
+        let mut named = schema.iter().any(Argument::is_named);
+        let mut positional = schema.iter().filter(|a| !a.is_named());
+        let mut current = positional.next();
+        // Always a suffix of `arg_str`, so positions can be worked out from
+        // how much is left.
+        let mut remaining = arg_str;
+        loop {
+            remaining = remaining.trim_left();
+            if remaining.is_empty() {
+                break;
+            }
+            let position = arg_str.len() - remaining.len();
+
+            if named {
+                if remaining.trim_right() == "--" || remaining.starts_with("-- ") {
+                    named = false;
+                    remaining = &remaining[2..];
+                    continue;
+                }
+                let before = remaining;
+                if let Some(given) = next_named(arg_str, &mut remaining)? {
+                    let argument = match find_named(schema, &given) {
+                        Some(a) => a,
+                        None => {
+                            let text = &before[..before.len() - remaining.len()];
+                            let problem = Problem::Unexpected(text.trim_right().to_string());
+                            return Err(ArgError::new(problem, arg_str, position));
+                        }
+                    };
+                    let value = match (argument.arity, given) {
+                        (Arity::Flag { .. }, Named::Long(_, Some(value))) => {
+                            let problem = Problem::Invalid {
+                                argument: argument.name,
+                                value: value,
+                                reason: "It's a flag, and doesn't take a value.".to_string(),
+                            };
+                            return Err(ArgError::new(problem, arg_str, position));
+                        }
+                        (Arity::Flag { .. }, _) => {
+                            Value {
+                                text: "true".to_string(),
+                                position: position,
+                            }
+                        }
+                        // The value starts after the `=`, since names can't
+                        // contain one.
+                        (_, Named::Long(_, Some(value))) => {
+                            Value {
+                                text: value,
+                                position: position + before.find('=').map_or(0, |i| i + 1),
+                            }
+                        }
+                        _ => {
+                            match next_value(arg_str, &mut remaining)? {
+                                Some(value) => value,
+                                None => {
+                                    let problem = Problem::Missing(argument.name);
+                                    return Err(ArgError::at_end(problem, arg_str));
+                                }
+                            }
+                        }
+                    };
+                    args.set(argument.name, value);
+                    continue;
+                }
+            }
+
+            let argument = match current {
+                Some(a) => a,
+                None => {
+                    let extra = next_value(arg_str, &mut remaining)?.map(|v| v.text);
+                    let problem = Problem::Unexpected(extra.unwrap_or_default());
+                    return Err(ArgError::new(problem, arg_str, position));
+                }
+            };
+            match argument.arity {
+                Arity::Rest => {
+                    args.push(argument.name,
+                              Value {
+                                  text: remaining.trim_right().to_string(),
+                                  position: position,
+                              });
+                    remaining = "";
+                }
+                Arity::Variadic => {
+                    if let Some(value) = next_value(arg_str, &mut remaining)? {
+                        args.push(argument.name, value);
+                    }
+                }
+                _ => {
+                    if let Some(value) = next_value(arg_str, &mut remaining)? {
+                        args.push(argument.name, value);
+                    }
+                    current = positional.next();
+                }
             }
-            _ => Err(Error::ArgParse),
         }
+
+        for argument in schema {
+            if args.raw(argument.name).is_empty() {
+                match argument.arity {
+                    Arity::Required => {
+                        return Err(ArgError::at_end(Problem::Missing(argument.name), arg_str))
+                    }
+                    Arity::Optional(Some(default)) |
+                    Arity::Named { default: Some(default), .. } => {
+                        let position = arg_str.trim_right().len();
+                        args.push(argument.name,
+                                  Value {
+                                      text: default.to_string(),
+                                      position: position,
+                                  })
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(args)
+    }
+
+    /// The arguments as a struct.
+    pub fn parse<T: FromArgs>(&self) -> ::std::result::Result<T, ArgError> {
+        T::from_args(self)
+    }
+
+    /// The value given for the argument `name`, or its default.
+    pub fn value<T>(&self, name: &'static str) -> ::std::result::Result<T, ArgError>
+        where T: FromStr,
+              T::Err: fmt::Display
+    {
+        match self.optional(name)? {
+            Some(value) => Ok(value),
+            None => Err(ArgError::at_end(Problem::Missing(name), &self.input)),
+        }
+    }
+
+    /// The value given for the argument `name` or its default, if either.
+    pub fn optional<T>(&self, name: &'static str) -> ::std::result::Result<Option<T>, ArgError>
+        where T: FromStr,
+              T::Err: fmt::Display
+    {
+        Ok(self.values(name)?.into_iter().next())
+    }
+
+    /// Whether the flag `name` was given.
+    pub fn flag(&self, name: &'static str) -> bool {
+        !self.raw(name).is_empty()
+    }
+
+    /// Every value given for the argument `name`.
+    pub fn values<T>(&self, name: &'static str) -> ::std::result::Result<Vec<T>, ArgError>
+        where T: FromStr,
+              T::Err: fmt::Display
+    {
+        self.convert(name, |text| text.parse::<T>().map_err(|e| e.to_string()))
+    }
+
+    /// The user, channel, role or emoji given for the argument `name`, by
+    /// mention or by name in `directory`. See `entities::resolve`.
+    pub fn entity<T: Entity>(&self,
+                             name: &'static str,
+                             directory: Option<&Directory>)
+                             -> ::std::result::Result<T, ArgError> {
+        match self.optional_entity(name, directory)? {
+            Some(entity) => Ok(entity),
+            None => Err(ArgError::at_end(Problem::Missing(name), &self.input)),
+        }
+    }
+
+    /// The same as `entity`, for arguments that can be left out.
+    pub fn optional_entity<T: Entity>(&self,
+                                      name: &'static str,
+                                      directory: Option<&Directory>)
+                                      -> ::std::result::Result<Option<T>, ArgError> {
+        Ok(self.entities(name, directory)?.into_iter().next())
+    }
+
+    /// Every user, channel, role or emoji given for the argument `name`.
+    pub fn entities<T: Entity>(&self,
+                               name: &'static str,
+                               directory: Option<&Directory>)
+                               -> ::std::result::Result<Vec<T>, ArgError> {
+        self.convert(name, |text| entities::resolve(text, directory))
+    }
+
+    /// Every value of the argument `name`, converted by `f`, which says
+    /// what's wrong with values it can't convert.
+    fn convert<T, F>(&self, name: &'static str, f: F) -> ::std::result::Result<Vec<T>, ArgError>
+        where F: Fn(&str) -> ::std::result::Result<T, String>
+    {
+        self.raw(name)
+            .iter()
+            .map(|value| {
+                f(&value.text).map_err(|reason| {
+                    let problem = Problem::Invalid {
+                        argument: name,
+                        value: value.text.clone(),
+                        reason: reason,
+                    };
+                    ArgError::new(problem, &self.input, value.position)
+                })
+            })
+            .collect()
+    }
+
+    /// Everything not yet parsed, as typed.
+    pub fn rest(&self) -> &str {
+        &self.unparsed
+    }
+
+    fn raw(&self, name: &str) -> &[Value] {
+        match self.values.iter().find(|&&(n, _)| n == name) {
+            Some(&(_, ref raw)) => raw,
+            None => &[],
+        }
+    }
+
+    fn push(&mut self, name: &'static str, value: Value) {
+        if let Some(&mut (_, ref mut raw)) = self.values.iter_mut().find(|&&mut (n, _)| n == name) {
+            raw.push(value);
+        }
+    }
+
+    /// Replace the value of `name`, since named arguments given twice take
+    /// the later value.
+    fn set(&mut self, name: &'static str, value: Value) {
+        if let Some(&mut (_, ref mut raw)) = self.values.iter_mut().find(|&&mut (n, _)| n == name) {
+            *raw = vec![value];
+        }
+    }
+
+    /// Take the next value off the front of the unparsed arguments. Values
+    /// are split up the same way as for `with_schema`, quotes and all.
+    pub fn single<T: FromStr>(&mut self) -> Result<T, T::Err>
+        where T::Err: StdError
+    {
+        let parsed = self.input.len() - self.unparsed.len();
+        let (value, rest) = match arg_parser::next_value(&self.unparsed) {
+            Ok(Some((value, rest))) => (value, rest.to_string()),
+            Ok(None) => return Err(Error::Empty),
+            Err(e) => {
+                return Err(Error::ArgParse(ParseError { position: parsed + e.position, ..e }))
+            }
+        };
+
+        let result = value.parse::<T>()?;
+        self.unparsed = rest;
+        Ok(result)
     }
 
+    /// The same as `single`, from when only this understood quotes.
     pub fn single_quoted<T: FromStr>(&mut self) -> Result<T, T::Err>
         where T::Err: StdError
     {
-        if self.unparsed.is_empty() {
-            return Err(Error::Empty);
+        self.single()
+    }
+}
+
+/// Take a named argument off the front of `remaining`, if there is one.
+/// `remaining` is the end of `input`.
+fn next_named(input: &str, remaining: &mut &str) -> ::std::result::Result<Option<Named>, ArgError> {
+    let offset = input.len() - remaining.len();
+    match arg_parser::named_arg(remaining) {
+        Ok(Some((named, rest))) => {
+            *remaining = rest;
+            Ok(Some(named))
+        }
+        Ok(None) => Ok(None),
+        Err(e) => Err(ArgError::new(Problem::Syntax(e.reason), input, offset + e.position)),
+    }
+}
+
+fn find_named<'a>(schema: &'a [Argument], given: &Named) -> Option<&'a Argument> {
+    schema.iter().find(|a| match given {
+                           &Named::Long(ref name, _) => a.is_named() && a.name == name,
+                           &Named::Short(c) => a.short() == Some(c),
+                       })
+}
+
+/// Take the next value, quoted or not, off the front of `remaining`.
+/// `remaining` is the end of `input`.
+fn next_value(input: &str, remaining: &mut &str) -> ::std::result::Result<Option<Value>, ArgError> {
+    let offset = input.len() - remaining.len();
+    let position = input.len() - remaining.trim_left().len();
+    match arg_parser::next_value(remaining) {
+        Ok(Some((text, rest))) => {
+            *remaining = rest;
+            Ok(Some(Value {
+                        text: text,
+                        position: position,
+                    }))
         }
+        Ok(None) => Ok(None),
+        Err(e) => Err(ArgError::new(Problem::Syntax(e.reason), input, offset + e.position)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &'static [Argument] = &[Argument {
+                                              name: "count",
+                                              description: "",
+                                              arity: Arity::Required,
+                                          },
+                                          Argument {
+                                              name: "sides",
+                                              description: "",
+                                              arity: Arity::Optional(Some("6")),
+                                          },
+                                          Argument {
+                                              name: "labels",
+                                              description: "",
+                                              arity: Arity::Variadic,
+                                          }];
+
+    fn argument(name: &'static str, arity: Arity) -> Argument {
+        Argument {
+            name: name,
+            description: "",
+            arity: arity,
+        }
+    }
+
+    #[test]
+    fn required_optional_and_variadic() {
+        let args = Args::with_schema("3 20 \"big one\" small", SCHEMA).unwrap();
+
+        assert_eq!(args.value::<u32>("count"), Ok(3));
+        assert_eq!(args.value::<u32>("sides"), Ok(20));
+        assert_eq!(args.values::<String>("labels"),
+                   Ok(vec!["big one".to_string(), "small".to_string()]));
+    }
+
+    #[test]
+    fn defaults_fill_in() {
+        let args = Args::with_schema("3", SCHEMA).unwrap();
+
+        assert_eq!(args.value::<u32>("sides"), Ok(6));
+        assert_eq!(args.values::<String>("labels"), Ok(vec![]));
+    }
 
-        let to_parse = self.unparsed.clone();
-        match arg_parser::single_quoted_arg(to_parse.as_bytes()) {
-            IResult::Done(rest, arg_str) => {
-                let result = arg_str.parse::<T>()?;
-                self.unparsed = str::from_utf8(rest).unwrap().to_string();
-                Ok(result)
+    fn problem(result: ::std::result::Result<Args, ArgError>) -> (Problem, usize) {
+        let error = result.unwrap_err();
+        (error.problem, error.position)
+    }
+
+    #[test]
+    fn missing_required() {
+        assert_eq!(problem(Args::with_schema("  ", SCHEMA)), (Problem::Missing("count"), 0));
+    }
+
+    #[test]
+    fn invalid_values_name_the_argument() {
+        let args = Args::with_schema("3  many", SCHEMA).unwrap();
+
+        let error = args.value::<u32>("sides").unwrap_err();
+        match error.problem {
+            Problem::Invalid { argument, ref value, .. } => {
+                assert_eq!(argument, "sides");
+                assert_eq!(value, "many");
             }
-            _ => Err(Error::ArgParse),
+            ref other => panic!("expected an invalid argument, got {:?}", other),
+        }
+        assert_eq!(error.position, 3);
+    }
+
+    #[test]
+    fn too_many_values() {
+        let schema = [argument("prefix", Arity::Required)];
+
+        assert_eq!(problem(Args::with_schema("? !", &schema)),
+                   (Problem::Unexpected("!".to_string()), 2));
+    }
+
+    #[test]
+    fn rest_is_as_typed() {
+        let schema = [argument("target", Arity::Required), argument("message", Arity::Rest)];
+
+        let args = Args::with_schema("me  say \"hi\"  there ", &schema).unwrap();
+        assert_eq!(args.value::<String>("message"), Ok("say \"hi\"  there".to_string()));
+
+        let args = Args::with_schema("me", &schema).unwrap();
+        assert_eq!(args.optional::<String>("message"), Ok(None));
+    }
+
+    #[test]
+    fn unclosed_quote() {
+        let schema = [argument("name", Arity::Required)];
+
+        assert_eq!(problem(Args::with_schema("\"oops", &schema)),
+                   (Problem::Syntax(Reason::UnterminatedQuote('"')), 0));
+        assert_eq!(problem(Args::with_schema("ok \"oops", &[argument("a", Arity::Variadic)])),
+                   (Problem::Syntax(Reason::UnterminatedQuote('"')), 3));
+    }
+
+    #[test]
+    fn excerpt_points_at_the_problem() {
+        let error = ArgError::new(Problem::Unexpected("x".to_string()), "one\ntwo x\nthree", 8);
+        assert_eq!(error.excerpt(), Some("```\ntwo x\n    ^\n```".to_string()));
+
+        let error = ArgError::new(Problem::Missing("name"), "\u{201C}é\u{201D}", 8);
+        assert_eq!(error.excerpt(), Some("```\n\u{201C}é\u{201D}\n   ^\n```".to_string()));
+
+        assert_eq!(ArgError::at_end(Problem::Missing("name"), " ").excerpt(), None);
+    }
+
+    #[test]
+    fn long_lines_are_cut_around_the_problem() {
+        let input = format!("{}x{}", "a".repeat(100), "b".repeat(100));
+        let error = ArgError::new(Problem::Unexpected("x".to_string()), &input, 100);
+
+        let excerpt = error.excerpt().unwrap();
+        let lines: Vec<&str> = excerpt.lines().collect();
+        assert_eq!(lines[1],
+                   format!("…{}x{}…", "a".repeat(30), "b".repeat(29)));
+        assert_eq!(lines[2], format!("{}^", " ".repeat(31)));
+    }
+
+    #[test]
+    fn no_schema_is_unchecked() {
+        let args = Args::with_schema("anything \"goes", &[]).unwrap();
+
+        assert_eq!(args.rest(), "anything \"goes");
+    }
+
+    struct Roll {
+        count: u32,
+        labels: Vec<String>,
+    }
+
+    impl FromArgs for Roll {
+        fn from_args(args: &Args) -> ::std::result::Result<Roll, ArgError> {
+            Ok(Roll {
+                   count: args.value("count")?,
+                   labels: args.values("labels")?,
+               })
+        }
+    }
+
+    #[test]
+    fn entities_by_mention_or_name() {
+        use discord::model::{RoleId, UserId};
+        use framework::entities::Entry;
+
+        let directory = Directory {
+            roles: vec![Entry::new(RoleId(7), vec!["Raiders".to_string()])],
+            ..Directory::default()
+        };
+        let schema = [argument("user", Arity::Required), argument("role", Arity::Required)];
+        let args = Args::with_schema("<@!5> raid", &schema).unwrap();
+
+        assert_eq!(args.entity("user", Some(&directory)), Ok(UserId(5)));
+        assert_eq!(args.entity("role", Some(&directory)), Ok(RoleId(7)));
+        let error = args.entity::<UserId>("role", Some(&directory)).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid `role`: There's no user named `raid`.");
+        assert_eq!(error.position, 6);
+    }
+
+    #[test]
+    fn parse_into_struct() {
+        let roll: Roll = Args::with_schema("2 6 a b", SCHEMA).unwrap().parse().unwrap();
+
+        assert_eq!(roll.count, 2);
+        assert_eq!(roll.labels, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    const NAMED: &'static [Argument] = &[Argument {
+                                             name: "name",
+                                             description: "",
+                                             arity: Arity::Optional(None),
+                                         },
+                                         Argument {
+                                             name: "kind",
+                                             description: "",
+                                             arity: Arity::Named {
+                                                 short: Some('k'),
+                                                 default: Some("any"),
+                                             },
+                                         },
+                                         Argument {
+                                             name: "upcoming",
+                                             description: "",
+                                             arity: Arity::Flag { short: Some('u') },
+                                         }];
+
+    #[test]
+    fn named_arguments_anywhere() {
+        for text in &["--kind maintenance --upcoming x",
+                      "x --upcoming --kind=maintenance",
+                      "-u x -k maintenance",
+                      "-k any --upcoming x --kind \"maintenance\""] {
+            let args = Args::with_schema(text, NAMED).unwrap();
+
+            assert_eq!(args.value::<String>("kind"), Ok("maintenance".to_string()), "{}", text);
+            assert!(args.flag("upcoming"), "{}", text);
+            assert_eq!(args.value::<String>("name"), Ok("x".to_string()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn named_defaults() {
+        let args = Args::with_schema("", NAMED).unwrap();
+
+        assert_eq!(args.value::<String>("kind"), Ok("any".to_string()));
+        assert!(!args.flag("upcoming"));
+        assert_eq!(args.optional::<String>("name"), Ok(None));
+    }
+
+    #[test]
+    fn named_argument_problems() {
+        assert_eq!(problem(Args::with_schema("x --color=red", NAMED)),
+                   (Problem::Unexpected("--color=red".to_string()), 2));
+        assert_eq!(problem(Args::with_schema("-z", NAMED)),
+                   (Problem::Unexpected("-z".to_string()), 0));
+        assert_eq!(problem(Args::with_schema("--kind ", NAMED)), (Problem::Missing("kind"), 6));
+        match problem(Args::with_schema("--upcoming=yes", NAMED)) {
+            (Problem::Invalid { argument: "upcoming", .. }, 0) => {}
+            other => panic!("expected an invalid flag, got {:?}", other),
+        }
+        assert_eq!(problem(Args::with_schema("--kind=\"oops", NAMED)),
+                   (Problem::Syntax(Reason::UnterminatedQuote('"')), 7));
+    }
+
+    #[test]
+    fn named_values_are_pointed_at() {
+        let schema = [argument("count",
+                               Arity::Named {
+                                   short: Some('c'),
+                                   default: None,
+                               })];
+
+        for &(text, caret) in &[("--count abc", "        ^"),
+                                ("--count=abc", "        ^"),
+                                ("-c  abc", "    ^")] {
+            let args = Args::with_schema(text, &schema).unwrap();
+            let error = args.value::<u32>("count").unwrap_err();
+
+            assert_eq!(error.excerpt(), Some(format!("```\n{}\n{}\n```", text, caret)));
+        }
+    }
+
+    #[test]
+    fn double_dash_ends_named_arguments() {
+        let args = Args::with_schema("-- --upcoming", NAMED).unwrap();
+
+        assert_eq!(args.value::<String>("name"), Ok("--upcoming".to_string()));
+        assert!(!args.flag("upcoming"));
+    }
+
+    #[test]
+    fn dashes_are_values_without_named_arguments() {
+        let schema = [argument("prefix", Arity::Required)];
+
+        let args = Args::with_schema("--", &schema).unwrap();
+        assert_eq!(args.value::<String>("prefix"), Ok("--".to_string()));
+    }
+
+    #[test]
+    fn quotes_from_phones() {
+        let schema = [argument("first", Arity::Required), argument("second", Arity::Required)];
+
+        let args = Args::with_schema("\u{201C}one two\u{201D} \u{2018}three\u{2019}", &schema)
+            .unwrap();
+        assert_eq!(args.value::<String>("first"), Ok("one two".to_string()));
+        assert_eq!(args.value::<String>("second"), Ok("three".to_string()));
+    }
+
+    #[test]
+    fn single_splits_like_schemas() {
+        let mut args = Args::new(" 'one two' `three` four");
+
+        assert_eq!(args.single::<String>().unwrap(), "one two");
+        assert_eq!(args.single_quoted::<String>().unwrap(), "three");
+        assert_eq!(args.single::<String>().unwrap(), "four");
+        match args.single::<String>() {
+            Err(Error::Empty) => {}
+            other => panic!("expected no more arguments, got {:?}", other),
         }
     }
 }
@@ -0,0 +1,327 @@
+use discord;
+use discord::model::{ChannelId, MessageId};
+use framework::args::{ArgError, Args};
+use framework::cooldown::Cooldown;
+use framework::dispatcher::Dispatcher;
+use framework::embed::Embed;
+use framework::permissions::Permission;
+use framework::ratelimit::RateLimiter;
+use framework::replies::{Replies, Reply, ReplyKind};
+use framework::transport::{IncomingMessage, Responder};
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum CommandError {
+    /// The arguments couldn't be understood. The message is shown to the user.
+    BadArguments(String),
+    /// The arguments didn't fit the command's `arguments`. Shown to the user
+    /// along with where the problem is.
+    Arguments(ArgError),
+    /// Something the command relies on (Discord, a website) failed.
+    Upstream(String),
+    /// The user isn't allowed to run the command. The message is shown to the user.
+    PermissionDenied(String),
+    /// A bug in the command.
+    Internal(String),
+    /// The command ran longer than its timeout and was cancelled.
+    TimedOut,
+    /// The command was used too often, and can be used again after this long.
+    CoolingDown(Duration),
+}
+
+impl StdError for CommandError {
+    fn description(&self) -> &str {
+        match self {
+            &CommandError::BadArguments(_) |
+            &CommandError::Arguments(_) => "Bad arguments",
+            &CommandError::Upstream(_) => "Upstream failure",
+            &CommandError::PermissionDenied(_) => "Permission denied",
+            &CommandError::Internal(_) => "Internal error",
+            &CommandError::TimedOut => "Timed out",
+            &CommandError::CoolingDown(_) => "Cooling down",
+        }
+    }
+}
+
+impl CommandError {
+    /// A short, stable name for the kind of error, for logs.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            &CommandError::BadArguments(_) |
+            &CommandError::Arguments(_) => "bad_arguments",
+            &CommandError::Upstream(_) => "upstream",
+            &CommandError::PermissionDenied(_) => "permission_denied",
+            &CommandError::Internal(_) => "internal",
+            &CommandError::TimedOut => "timed_out",
+            &CommandError::CoolingDown(_) => "cooldown",
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &CommandError::BadArguments(ref m) => write!(f, "Bad arguments: {}", m),
+            &CommandError::Arguments(ref e) => write!(f, "Bad arguments: {}", e),
+            &CommandError::Upstream(ref m) => write!(f, "Upstream failure: {}", m),
+            &CommandError::PermissionDenied(ref m) => write!(f, "Permission denied: {}", m),
+            &CommandError::Internal(ref m) => write!(f, "Internal error: {}", m),
+            &CommandError::TimedOut => write!(f, "Timed out"),
+            &CommandError::CoolingDown(wait) => {
+                write!(f, "Cooling down for {}s", wait_seconds(wait))
+            }
+        }
+    }
+}
+
+impl From<discord::Error> for CommandError {
+    fn from(e: discord::Error) -> Self {
+        CommandError::Upstream(format!("Discord: {}", e))
+    }
+}
+
+impl From<ArgError> for CommandError {
+    fn from(e: ArgError) -> Self {
+        CommandError::Arguments(e)
+    }
+}
+
+pub type CommandResult = Result<(), CommandError>;
+
+/// `wait` in seconds, rounded up so it's never "0 seconds".
+pub fn wait_seconds(wait: Duration) -> u64 {
+    wait.as_secs() + if wait.subsec_nanos() > 0 { 1 } else { 0 }
+}
+
+/// Everything a command needs to know about the message that invoked it.
+pub struct Context<'a> {
+    pub responder: Arc<Responder>,
+    pub message: &'a IncomingMessage,
+    /// The prefix in effect where the message was sent.
+    pub prefix: &'a str,
+    pub dispatcher: &'a Dispatcher,
+    /// Set once the command has run past its timeout. Nothing more is sent
+    /// for it after that.
+    pub timed_out: Arc<AtomicBool>,
+    /// Replies sent so far, and when the message was edited, the replies to
+    /// it from before that are edited instead of sending new ones.
+    pub replies: Arc<Replies>,
+}
+
+impl<'a> Context<'a> {
+    /// Send `text` to the channel the command came from, waiting first if
+    /// the bot is sending too quickly.
+    pub fn send_message(&self, text: &str) -> Result<MessageId, CommandError> {
+        self.check_timeout()?;
+        send_text(&*self.responder,
+                  self.dispatcher.rate_limiter(),
+                  &self.replies,
+                  self.message.channel_id,
+                  text)
+    }
+
+    /// Send an embed to the channel the command came from, waiting first if
+    /// the bot is sending too quickly.
+    pub fn send_embed(&self, embed: &Embed) -> Result<MessageId, CommandError> {
+        self.check_timeout()?;
+        let channel = self.message.channel_id;
+        self.dispatcher.rate_limiter().wait(channel);
+        let id = match self.replies.reuse(ReplyKind::Embed) {
+            Some(id) => {
+                self.responder.edit_embed(channel, id, embed)?;
+                id
+            }
+            None => self.responder.send_embed(channel, embed)?,
+        };
+        self.replies.record(Reply {
+                                id: id,
+                                kind: ReplyKind::Embed,
+                            });
+        Ok(id)
+    }
+
+    /// React to the message that invoked the command.
+    pub fn react(&self, emoji: &str) -> CommandResult {
+        self.check_timeout()?;
+        self.dispatcher.rate_limiter().wait(self.message.channel_id);
+        self.responder.react(self.message.channel_id, self.message.id, emoji)
+    }
+
+    /// Send `text` privately to whoever invoked the command.
+    pub fn reply_privately(&self, text: &str) -> Result<MessageId, CommandError> {
+        self.check_timeout()?;
+        self.responder.send_private_message(self.message.author.id, text)
+    }
+
+    /// Fails once the command has run past its timeout, so long running
+    /// commands can stop early.
+    pub fn check_timeout(&self) -> CommandResult {
+        if self.timed_out.load(Ordering::SeqCst) {
+            return Err(CommandError::TimedOut);
+        }
+
+        Ok(())
+    }
+}
+
+/// Send `text` to `channel` as one of `replies`, editing a reply from before
+/// the command was edited if there's one to reuse, and waiting first if the
+/// bot is sending too quickly.
+pub fn send_text(responder: &Responder,
+                 rate_limiter: &RateLimiter,
+                 replies: &Replies,
+                 channel: ChannelId,
+                 text: &str)
+                 -> Result<MessageId, CommandError> {
+    rate_limiter.wait(channel);
+    let id = match replies.reuse(ReplyKind::Text) {
+        Some(id) => {
+            responder.edit_message(channel, id, text)?;
+            id
+        }
+        None => responder.send_message(channel, text)?,
+    };
+    replies.record(Reply {
+                       id: id,
+                       kind: ReplyKind::Text,
+                   });
+    Ok(id)
+}
+
+/// Describes one argument a command takes. The dispatcher matches what the
+/// user typed to the command's arguments before running it, and they're
+/// listed in help and usage messages.
+pub struct Argument {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub arity: Arity,
+}
+
+impl Argument {
+    /// Whether the command can be used without giving this argument.
+    pub fn is_optional(&self) -> bool {
+        self.arity != Arity::Required
+    }
+
+    /// Whether the argument is given by name, like `--name`, rather than by
+    /// position.
+    pub fn is_named(&self) -> bool {
+        match self.arity {
+            Arity::Flag { .. } | Arity::Named { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// The single letter the argument can also be given by, like `-n`.
+    pub fn short(&self) -> Option<char> {
+        match self.arity {
+            Arity::Flag { short } | Arity::Named { short, .. } => short,
+            _ => None,
+        }
+    }
+}
+
+/// How many values an argument takes, and whether it's given by position
+/// or by name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arity {
+    /// Exactly one value, which must be given.
+    Required,
+    /// One value, or none. When none is given the default is used, if
+    /// there is one.
+    Optional(Option<&'static str>),
+    /// Any number of values, including none. Only the last positional
+    /// argument can be variadic.
+    Variadic,
+    /// Everything after the arguments before it, as typed. Only the last
+    /// positional argument can take the rest.
+    Rest,
+    /// `--name` on its own, to turn something on.
+    Flag { short: Option<char> },
+    /// `--name value`, which can be left out, in which case the default is
+    /// used if there is one.
+    Named {
+        short: Option<char>,
+        default: Option<&'static str>,
+    },
+}
+
+/// Commands are shared by every shard's thread, so must be `Send + Sync`.
+pub trait Command: Send + Sync {
+    /// The name used to invoke the command, without the prefix.
+    fn name(&self) -> &'static str;
+
+    /// The heading the command is listed under in help.
+    fn category(&self) -> &'static str {
+        "General"
+    }
+
+    /// Alternate names the command can be invoked with.
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn arguments(&self) -> &'static [Argument] {
+        &[]
+    }
+
+    /// Argument synopsis shown after the command name, when the one built
+    /// from `arguments` isn't descriptive enough.
+    fn usage(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Complete example invocations, without the prefix.
+    fn examples(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// What a user needs before the dispatcher will run the command.
+    fn permissions(&self) -> &'static [Permission] {
+        &[]
+    }
+
+    /// How often the command may be used. Uses past the limit get a reply
+    /// saying when to try again instead of running the command.
+    fn cooldown(&self) -> Option<Cooldown> {
+        None
+    }
+
+    /// How long the command may run before it's cancelled and the user is
+    /// told it took too long.
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(30)
+    }
+
+    fn exec(&self, ctx: &Context, args: Args) -> CommandResult;
+}
+
+/// The argument synopsis for `command`, e.g. `<prefix>` or `[dice]`.
+pub fn usage(command: &Command) -> Option<String> {
+    if let Some(usage) = command.usage() {
+        return Some(usage.to_string());
+    }
+    if command.arguments().is_empty() {
+        return None;
+    }
+
+    let synopsis: Vec<String> = command.arguments()
+        .iter()
+        .map(|a| match a.arity {
+                 Arity::Required => format!("<{}>", a.name),
+                 Arity::Optional(_) => format!("[{}]", a.name),
+                 Arity::Variadic | Arity::Rest => format!("[{}...]", a.name),
+                 Arity::Flag { .. } => format!("[--{}]", a.name),
+                 Arity::Named { .. } => format!("[--{} <{}>]", a.name, a.name),
+             })
+        .collect();
+    Some(synopsis.join(" "))
+}
@@ -0,0 +1,205 @@
+use discord::model::{ChannelId, MessageId, UserId};
+use framework::command::{CommandError, CommandResult};
+use framework::dispatcher::Dispatcher;
+use framework::embed::Embed;
+use framework::transport::{Author, IncomingMessage, Responder};
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+/// The bot's user ID in console mode.
+pub const BOT_USER: UserId = UserId(1);
+/// Whoever is typing at the console.
+pub const CONSOLE_USER: UserId = UserId(2);
+const CONSOLE_CHANNEL: ChannelId = ChannelId(1);
+
+/// Prints replies as text instead of sending them to Discord.
+pub struct ConsoleResponder<W: Write> {
+    out: Mutex<W>,
+    sent: Mutex<u64>,
+}
+
+impl<W: Write> ConsoleResponder<W> {
+    pub fn new(out: W) -> ConsoleResponder<W> {
+        ConsoleResponder {
+            out: Mutex::new(out),
+            sent: Mutex::new(0),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out.into_inner().unwrap()
+    }
+
+    fn print(&self, text: &str) -> Result<MessageId, CommandError> {
+        let mut out = self.out.lock().unwrap();
+        writeln!(out, "{}", text)
+            .and_then(|_| out.flush())
+            .map_err(|e| CommandError::Upstream(format!("Console: {}", e)))?;
+
+        let mut sent = self.sent.lock().unwrap();
+        *sent += 1;
+        Ok(MessageId(*sent))
+    }
+}
+
+impl<W: Write + Send> Responder for ConsoleResponder<W> {
+    fn user_id(&self) -> UserId {
+        BOT_USER
+    }
+
+    fn send_message(&self, _channel: ChannelId, text: &str) -> Result<MessageId, CommandError> {
+        self.print(text)
+    }
+
+    fn send_embed(&self, _channel: ChannelId, embed: &Embed) -> Result<MessageId, CommandError> {
+        self.print(&render_embed(embed))
+    }
+
+    fn edit_message(&self, _channel: ChannelId, message: MessageId, text: &str) -> CommandResult {
+        self.print(&format!("(edited {}) {}", message, text))?;
+        Ok(())
+    }
+
+    fn edit_embed(&self, _channel: ChannelId, message: MessageId, embed: &Embed) -> CommandResult {
+        self.print(&format!("(edited {})\n{}", message, render_embed(embed)))?;
+        Ok(())
+    }
+
+    fn delete_message(&self, _channel: ChannelId, message: MessageId) -> CommandResult {
+        self.print(&format!("(deleted {})", message))?;
+        Ok(())
+    }
+
+    fn react(&self, _channel: ChannelId, _message: MessageId, emoji: &str) -> Result<(), CommandError> {
+        self.print(&format!("(reacted with {})", emoji))?;
+        Ok(())
+    }
+
+    fn send_private_message(&self, _user: UserId, text: &str) -> Result<MessageId, CommandError> {
+        self.print(&format!("(privately) {}", text))
+    }
+}
+
+/// Run each line of `input` through `dispatcher` as if it had been sent in a
+/// private channel by an owner, until the input ends or a command asks the
+/// bot to stop.
+pub fn run<R, W>(dispatcher: &Dispatcher,
+                 input: R,
+                 responder: Arc<ConsoleResponder<W>>)
+                 -> io::Result<()>
+    where R: BufRead,
+          W: Write + Send + 'static
+{
+    for (i, line) in input.lines().enumerate() {
+        let message = IncomingMessage {
+            id: MessageId(i as u64 + 1),
+            channel_id: CONSOLE_CHANNEL,
+            server_id: None,
+            author: Author {
+                id: CONSOLE_USER,
+                name: "console".to_string(),
+            },
+            member: None,
+            directory: None,
+            content: line?,
+        };
+        dispatcher.dispatch(responder.clone(), &message);
+        if dispatcher.shutdown().is_requested() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// `embed` as plain text, e.g.
+///
+/// ```text
+/// == FF XIV Resets ==
+/// Daily:
+///   3 hours, 2 minutes
+/// ```
+pub fn render_embed(embed: &Embed) -> String {
+    let mut lines = Vec::new();
+    if let Some(ref title) = embed.title {
+        lines.push(format!("== {} ==", title));
+    }
+    if let Some(ref description) = embed.description {
+        lines.push(description.clone());
+    }
+    for field in &embed.fields {
+        lines.push(format!("{}:", field.name));
+        lines.extend(field.value.lines().map(|l| format!("  {}", l)));
+    }
+    if let Some(ref footer) = embed.footer {
+        lines.push(format!("-- {}", footer));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use framework::args::Args;
+    use framework::command::{Command, CommandResult, Context};
+    use framework::prefixes::Prefixes;
+    use framework::registry::CommandRegistry;
+    use framework::shutdown::{ShutdownKind, ShutdownRequest};
+
+    #[test]
+    fn render_full_embed() {
+        let embed = Embed::new()
+            .title("Title")
+            .description("About this")
+            .field("One", "first\nsecond", false)
+            .field("Two", "third", true)
+            .footer("Page 1 of 1");
+
+        assert_eq!(render_embed(&embed),
+                   "== Title ==\nAbout this\nOne:\n  first\n  second\nTwo:\n  third\n-- Page 1 of 1");
+    }
+
+    #[test]
+    fn render_empty_embed() {
+        assert_eq!(render_embed(&Embed::new()), "");
+    }
+
+    struct Echo;
+
+    impl Command for Echo {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn exec(&self, ctx: &Context, args: Args) -> CommandResult {
+            if args.rest() == "stop" {
+                ctx.dispatcher.shutdown().request(ShutdownRequest::new(ShutdownKind::Stop));
+            }
+            ctx.send_message(args.rest())?;
+            Ok(())
+        }
+    }
+
+    fn run_lines(input: &str) -> String {
+        let mut registry = CommandRegistry::new();
+        registry.register(Echo);
+        let dispatcher = Dispatcher::new(registry, Prefixes::new("!"), vec![]);
+        let responder = Arc::new(ConsoleResponder::new(Vec::new()));
+
+        run(&dispatcher, input.as_bytes(), responder.clone()).unwrap();
+
+        let output = Arc::try_unwrap(responder).ok().expect("nothing else holds the responder");
+        String::from_utf8(output.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn run_prints_replies() {
+        assert_eq!(run_lines("!echo one\nnot a command\n!echo two\n"), "one\ntwo\n");
+    }
+
+    #[test]
+    fn run_stops_on_shutdown() {
+        assert_eq!(run_lines("!echo stop\n!echo more\n"), "stop\n");
+    }
+}
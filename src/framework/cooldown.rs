@@ -0,0 +1,270 @@
+use discord::model::{ChannelId, MessageId, ServerId, UserId};
+use framework::command::{CommandError, CommandResult, Context};
+use framework::middleware::{Invocation, Middleware};
+use framework::ratelimit::SlidingWindow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Who shares a command's cooldown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bucket {
+    User,
+    Channel,
+    /// Everyone in the server. Private channels count as their own server.
+    Server,
+}
+
+/// Allows a command `uses` times in a burst, then no more until the oldest
+/// use is `per` old.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cooldown {
+    pub bucket: Bucket,
+    pub uses: usize,
+    pub per: Duration,
+}
+
+impl fmt::Display for Cooldown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bucket = match self.bucket {
+            Bucket::User => "user",
+            Bucket::Channel => "channel",
+            Bucket::Server => "server",
+        };
+        write!(f,
+               "{} use{} every {} seconds per {}",
+               self.uses,
+               if self.uses == 1 { "" } else { "s" },
+               self.per.as_secs(),
+               bucket)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum BucketKey {
+    User(UserId),
+    Channel(ChannelId),
+    Server(ServerId),
+}
+
+/// Once this many buckets are being tracked, idle ones are forgotten.
+const MAX_IDLE_BUCKETS: usize = 1000;
+
+/// One use counted against a cooldown, which can be given back with
+/// `Cooldowns::refund`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Use {
+    bucket: (String, BucketKey),
+    at: Instant,
+}
+
+pub struct Cooldowns {
+    windows: Mutex<HashMap<(String, BucketKey), SlidingWindow>>,
+    /// Uses by commands that are still running, by the message and command,
+    /// in case they turn out not to count.
+    running: Mutex<HashMap<(MessageId, String), Use>>,
+}
+
+impl Cooldowns {
+    pub fn new() -> Cooldowns {
+        Cooldowns {
+            windows: Mutex::new(HashMap::new()),
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a use of the command at `path`, or say how long until it can be
+    /// used again.
+    pub fn try_use(&self,
+                   path: &str,
+                   cooldown: &Cooldown,
+                   user: UserId,
+                   channel: ChannelId,
+                   server: Option<ServerId>,
+                   now: Instant)
+                   -> Result<Use, Duration> {
+        let bucket = (path.to_string(), bucket_key(cooldown.bucket, user, channel, server));
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() > MAX_IDLE_BUCKETS {
+            windows.retain(|_, w| !w.is_idle(now));
+        }
+        windows.entry(bucket.clone())
+            .or_insert_with(|| SlidingWindow::new(cooldown.uses, cooldown.per))
+            .try_use(now)?;
+        Ok(Use {
+               bucket: bucket,
+               at: now,
+           })
+    }
+
+    /// Give back `used`, for uses that shouldn't count. Other uses of the
+    /// same bucket, like by others in the channel, still count.
+    pub fn refund(&self, used: &Use) {
+        if let Some(window) = self.windows.lock().unwrap().get_mut(&used.bucket) {
+            window.forget(used.at);
+        }
+    }
+}
+
+fn bucket_key(bucket: Bucket,
+              user: UserId,
+              channel: ChannelId,
+              server: Option<ServerId>)
+              -> BucketKey {
+    match (bucket, server) {
+        (Bucket::User, _) => BucketKey::User(user),
+        (Bucket::Channel, _) |
+        (Bucket::Server, None) => BucketKey::Channel(channel),
+        (Bucket::Server, Some(s)) => BucketKey::Server(s),
+    }
+}
+
+/// Stops commands being used more often than their `Command::cooldown` allows.
+/// Uses with arguments the command couldn't make sense of don't count, so a
+/// typo doesn't cost anyone their turn.
+impl Middleware for Cooldowns {
+    fn before(&self, ctx: &Context, invocation: &Invocation) -> CommandResult {
+        let cooldown = match invocation.command.cooldown() {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+
+        let used = self.try_use(invocation.path,
+                                &cooldown,
+                                ctx.message.author.id,
+                                ctx.message.channel_id,
+                                ctx.message.server_id,
+                                Instant::now())
+            .map_err(CommandError::CoolingDown)?;
+        self.running.lock().unwrap().insert((ctx.message.id, invocation.path.to_string()), used);
+        Ok(())
+    }
+
+    fn after(&self,
+             ctx: &Context,
+             invocation: &Invocation,
+             result: &CommandResult,
+             _duration: Duration) {
+        let key = (ctx.message.id, invocation.path.to_string());
+        let used = match self.running.lock().unwrap().remove(&key) {
+            Some(used) => used,
+            None => return,
+        };
+
+        match result {
+            &Err(CommandError::Arguments(_)) |
+            &Err(CommandError::BadArguments(_)) => self.refund(&used),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cooldown(bucket: Bucket) -> Cooldown {
+        Cooldown {
+            bucket: bucket,
+            uses: 2,
+            per: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn user_bucket() {
+        let cooldowns = Cooldowns::new();
+        let now = Instant::now();
+        let user = cooldown(Bucket::User);
+
+        assert!(cooldowns.try_use("roll", &user, UserId(1), ChannelId(1), None, now).is_ok());
+        assert!(cooldowns.try_use("roll", &user, UserId(1), ChannelId(2), None, now).is_ok());
+        assert_eq!(cooldowns.try_use("roll", &user, UserId(1), ChannelId(3), None, now),
+                   Err(Duration::from_secs(60)));
+        assert!(cooldowns.try_use("roll", &user, UserId(2), ChannelId(1), None, now).is_ok());
+    }
+
+    #[test]
+    fn channel_bucket() {
+        let cooldowns = Cooldowns::new();
+        let now = Instant::now();
+        let channel = cooldown(Bucket::Channel);
+
+        assert!(cooldowns.try_use("events", &channel, UserId(1), ChannelId(1), None, now).is_ok());
+        assert!(cooldowns.try_use("events", &channel, UserId(2), ChannelId(1), None, now).is_ok());
+        assert!(cooldowns.try_use("events", &channel, UserId(3), ChannelId(1), None, now).is_err());
+        assert!(cooldowns.try_use("events", &channel, UserId(3), ChannelId(2), None, now).is_ok());
+    }
+
+    #[test]
+    fn server_bucket() {
+        let cooldowns = Cooldowns::new();
+        let now = Instant::now();
+        let server = cooldown(Bucket::Server);
+        let in_server = Some(ServerId(1));
+
+        assert!(cooldowns.try_use("events", &server, UserId(1), ChannelId(1), in_server, now)
+                    .is_ok());
+        assert!(cooldowns.try_use("events", &server, UserId(2), ChannelId(2), in_server, now)
+                    .is_ok());
+        assert!(cooldowns.try_use("events", &server, UserId(3), ChannelId(3), in_server, now)
+                    .is_err());
+        assert!(cooldowns.try_use("events", &server, UserId(3), ChannelId(4), None, now).is_ok());
+    }
+
+    #[test]
+    fn refunded_uses_do_not_count() {
+        let cooldowns = Cooldowns::new();
+        let now = Instant::now();
+        let user = cooldown(Bucket::User);
+
+        assert!(cooldowns.try_use("roll", &user, UserId(1), ChannelId(1), None, now).is_ok());
+        let used = cooldowns.try_use("roll", &user, UserId(1), ChannelId(1), None, now).unwrap();
+        cooldowns.refund(&used);
+        assert!(cooldowns.try_use("roll", &user, UserId(1), ChannelId(1), None, now).is_ok());
+        assert!(cooldowns.try_use("roll", &user, UserId(1), ChannelId(1), None, now).is_err());
+    }
+
+    #[test]
+    fn refunds_leave_others_uses_alone() {
+        let cooldowns = Cooldowns::new();
+        let start = Instant::now();
+        let later = start + Duration::from_secs(10);
+        let channel = cooldown(Bucket::Channel);
+
+        let first = cooldowns.try_use("events", &channel, UserId(1), ChannelId(1), None, start)
+            .unwrap();
+        assert!(cooldowns.try_use("events", &channel, UserId(2), ChannelId(1), None, later)
+                    .is_ok());
+        cooldowns.refund(&first);
+
+        // Only the second user's use is left, so the wait is counted from it.
+        assert!(cooldowns.try_use("events", &channel, UserId(3), ChannelId(1), None, later)
+                    .is_ok());
+        assert_eq!(cooldowns.try_use("events", &channel, UserId(3), ChannelId(1), None, later),
+                   Err(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn commands_have_separate_cooldowns() {
+        let cooldowns = Cooldowns::new();
+        let now = Instant::now();
+        let user = Cooldown {
+            bucket: Bucket::User,
+            uses: 1,
+            per: Duration::from_secs(60),
+        };
+
+        assert!(cooldowns.try_use("roll", &user, UserId(1), ChannelId(1), None, now).is_ok());
+        assert!(cooldowns.try_use("8-ball", &user, UserId(1), ChannelId(1), None, now).is_ok());
+        assert!(cooldowns.try_use("roll", &user, UserId(1), ChannelId(1), None, now).is_err());
+        assert!(cooldowns.try_use("roll",
+                                  &user,
+                                  UserId(1),
+                                  ChannelId(1),
+                                  None,
+                                  now + Duration::from_secs(60))
+                    .is_ok());
+    }
+}
@@ -0,0 +1,714 @@
+use discord::model::{ChannelId, UserId};
+use framework::args::Args;
+use framework::command::{self, Command, CommandError, CommandResult, Context};
+use framework::cooldown::Cooldowns;
+use framework::middleware::{Invocation, LogCommands, Middleware, RecordMetrics};
+use framework::permissions::CheckPermissions;
+use framework::pool::PoolHandle;
+use framework::prefixes::Prefixes;
+use framework::ratelimit::RateLimiter;
+use framework::registry::{CommandRegistry, Lookup};
+use framework::replies::{RecentReplies, Replies, Reply};
+use framework::shutdown::{Shutdown, ShutdownKind, ShutdownRequest};
+use framework::transport::{IncomingMessage, Responder};
+use framework::watchdog::Watchdog;
+use gateway::shards::Shards;
+use logging;
+use metrics::Metrics;
+use storage::Storage;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How many commands' replies are remembered, so they can be edited when the
+/// command is.
+const RECENT_REPLIES: usize = 1000;
+/// How long after a command its message can be edited to run it again.
+const EDIT_WINDOW_SECS: u64 = 10 * 60;
+
+pub struct Dispatcher {
+    registry: CommandRegistry,
+    prefixes: Prefixes,
+    middleware: Vec<Box<Middleware>>,
+    rate_limiter: Arc<RateLimiter>,
+    shards: Shards,
+    storage: Arc<Storage>,
+    metrics: Arc<Metrics>,
+    watchdog: Watchdog,
+    shutdown: Shutdown,
+    replies: RecentReplies,
+}
+
+impl Dispatcher {
+    /// A dispatcher that logs and counts every command, and checks
+    /// permissions (`owners` are the bot's owners) and cooldowns before
+    /// running it.
+    pub fn new(registry: CommandRegistry, prefixes: Prefixes, owners: Vec<UserId>) -> Dispatcher {
+        let metrics = Arc::new(Metrics::new());
+        Dispatcher {
+            registry: registry,
+            prefixes: prefixes,
+            middleware: vec![Box::new(LogCommands),
+                             Box::new(RecordMetrics(metrics.clone())),
+                             Box::new(CheckPermissions::new(owners)),
+                             Box::new(Cooldowns::new())],
+            rate_limiter: Arc::new(RateLimiter::new()),
+            shards: Shards::default(),
+            storage: Arc::new(Storage::in_memory()),
+            metrics: metrics,
+            watchdog: Watchdog::new(),
+            shutdown: Shutdown::new(),
+            replies: RecentReplies::new(RECENT_REPLIES,
+                                        Duration::from_secs(EDIT_WINDOW_SECS)),
+        }
+    }
+
+    pub fn registry(&self) -> &CommandRegistry {
+        &self.registry
+    }
+
+    pub fn prefixes(&self) -> &Prefixes {
+        &self.prefixes
+    }
+
+    /// Wrap every command in `middleware`, inside what's already there: its
+    /// before hook only runs once permissions and cooldowns have been checked.
+    pub fn add_middleware<M: Middleware + 'static>(&mut self, middleware: M) {
+        self.middleware.push(Box::new(middleware));
+    }
+
+    /// Shared by everything that sends messages, to stay within Discord's limits.
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// The gateway connections feeding the dispatcher. Empty when running
+    /// without Discord.
+    pub fn shards(&self) -> &Shards {
+        &self.shards
+    }
+
+    pub fn set_shards(&mut self, shards: Shards) {
+        self.shards = shards;
+    }
+
+    /// Where commands keep state between restarts. In memory only, unless
+    /// `set_storage` says otherwise.
+    pub fn storage(&self) -> &Arc<Storage> {
+        &self.storage
+    }
+
+    pub fn set_storage(&mut self, storage: Arc<Storage>) {
+        self.storage = storage;
+    }
+
+    /// Counts of what commands have been doing, for monitoring.
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Whether the bot is stopping, and which commands it's waiting for.
+    /// Nothing is dispatched once a shutdown is requested.
+    pub fn shutdown(&self) -> &Shutdown {
+        &self.shutdown
+    }
+
+    /// Stop cleanly for `request`: wait up to `deadline` for running commands,
+    /// save storage, then confirm to whoever asked.
+    pub fn finish_shutdown(&self, request: &ShutdownRequest, deadline: Duration) {
+        // In case something other than a request is why we're stopping.
+        self.shutdown.request(request.clone());
+        let unfinished = self.shutdown.wait_idle(deadline);
+        if unfinished > 0 {
+            warn!("Stopping with {} command{} still running.",
+                  unfinished,
+                  if unfinished == 1 { "" } else { "s" });
+        }
+        let saved = self.storage.flush();
+        if let Err(ref e) = saved {
+            error!("Unable to save storage: {}", e);
+        }
+
+        if let Some((ref responder, channel)) = request.reply_to {
+            let text = match (saved, request.kind) {
+                (Err(_), _) => "Stopping, but couldn't save everything. See the logs for why.",
+                (Ok(()), ShutdownKind::Stop) => "Shutting down.",
+                (Ok(()), ShutdownKind::Restart) => "Restarting.",
+            };
+            if let Err(e) = responder.send_message(channel, text) {
+                warn!("Unable to reply in channel {}: {}", channel, e);
+            }
+        }
+    }
+
+    /// Run the command `message` invokes, if any. Commands are recognized by
+    /// the server's prefix, or by mentioning the bot. When a message with the
+    /// prefix looks like a mistyped command, reply with the closest known
+    /// command instead. Mentions aren't corrected, since they're more often
+    /// just talking to the bot.
+    pub fn dispatch(&self, responder: Arc<Responder>, message: &IncomingMessage) {
+        self.handle(responder, message, Vec::new());
+    }
+
+    /// Run the command again after `message` was edited, editing the replies
+    /// from last time instead of sending new ones. Messages that weren't
+    /// recent commands are ignored, so editing an old message or ordinary
+    /// chat into a command does nothing, and so are updates that didn't
+    /// change what the command says.
+    pub fn dispatch_edit(&self, responder: Arc<Responder>, message: &IncomingMessage) {
+        if let Some(previous) = self.replies.changed(message.id, &message.content, Instant::now()) {
+            self.handle(responder, message, previous);
+        }
+    }
+
+    /// Whether `message` is for the bot `bot`, by the server's prefix or a
+    /// mention, so worth looking at more closely.
+    pub fn is_addressed(&self, message: &IncomingMessage, bot: UserId) -> bool {
+        let prefix = self.prefixes.get(message.server_id);
+        command_text(&message.content, &prefix, bot).is_some()
+    }
+
+    fn handle(&self, responder: Arc<Responder>, message: &IncomingMessage, previous: Vec<Reply>) {
+        let _running = match self.shutdown.start() {
+            Some(r) => r,
+            None => return,
+        };
+        let prefix = self.prefixes.get(message.server_id);
+        let (command_str, mentioned) =
+            match command_text(&message.content, &prefix, responder.user_id()) {
+                Some(found) => found,
+                None => return,
+            };
+        let _fields = logging::scope(message_fields(message));
+        let ctx = Context {
+            responder: responder,
+            message: message,
+            prefix: &prefix,
+            dispatcher: self,
+            timed_out: Arc::new(AtomicBool::new(false)),
+            replies: Arc::new(Replies::new(previous)),
+        };
+
+        self.respond(&ctx, command_str, mentioned);
+        // Replies from last time that this run had no use for would be left
+        // answering a command that no longer says that.
+        for reply in ctx.replies.unused() {
+            if let Err(e) = ctx.responder.delete_message(message.channel_id, reply.id) {
+                warn!("Unable to delete old reply {}: {}", reply.id, e);
+            }
+        }
+        self.replies.record(message.id, &message.content, ctx.replies.sent(), Instant::now());
+    }
+
+    fn respond(&self, ctx: &Context, command_str: &str, mentioned: bool) {
+        let prefix = ctx.prefix;
+        match self.registry.find(command_str) {
+            Some(Lookup::Command { path, command, args }) => self.exec(ctx, &path, command, args),
+            Some(Lookup::Group { path, group, args }) => {
+                if args.is_empty() {
+                    if let Some(command) = group.default_command() {
+                        let path = format!("{} {}", path, command.name());
+                        return self.exec(ctx, &path, command, args);
+                    }
+                }
+
+                let text = match group.commands().suggest(args) {
+                    Some(suggestion) => {
+                        format!("Unknown command. Did you mean `{}{} {}`?",
+                                prefix,
+                                path,
+                                suggestion)
+                    }
+                    None => {
+                        format!("`{}{}` needs a subcommand. See `{}help {}`.",
+                                prefix,
+                                path,
+                                prefix,
+                                path)
+                    }
+                };
+                reply(ctx, &text);
+            }
+            None if mentioned => {}
+            None => {
+                if let Some(suggestion) = self.registry.suggest(command_str) {
+                    reply(ctx,
+                          &format!("Unknown command. Did you mean `{}{}`?", prefix, suggestion));
+                }
+            }
+        }
+    }
+
+    fn exec(&self, ctx: &Context, path: &str, command: &Command, args: &str) {
+        let _fields = logging::scope(vec![("command", path.to_string())]);
+        let invocation = Invocation {
+            path: path,
+            command: command,
+            args: args,
+        };
+        let started = Instant::now();
+        let result = self.before(ctx, &invocation).and_then(|()| self.run(ctx, &invocation));
+        let duration = started.elapsed();
+
+        // Once timed out, the user has already been told.
+        if let Err(ref e) = result {
+            if !ctx.timed_out.load(Ordering::SeqCst) {
+                reply(ctx, &error_reply(ctx.prefix, path, command, e));
+            }
+        }
+        for middleware in self.middleware.iter().rev() {
+            middleware.after(ctx, &invocation, &result, duration);
+        }
+    }
+
+    fn before(&self, ctx: &Context, invocation: &Invocation) -> CommandResult {
+        for middleware in &self.middleware {
+            middleware.before(ctx, invocation)?;
+        }
+
+        Ok(())
+    }
+
+    /// Run the command, cancelling it if it runs past its timeout.
+    fn run(&self, ctx: &Context, invocation: &Invocation) -> CommandResult {
+        let command = invocation.command;
+        let args = Args::with_schema(invocation.args, command.arguments())?;
+        // Whichever of the command and the watchdog gets here first decides
+        // whether the command finished in time.
+        let settled = Arc::new(AtomicBool::new(false));
+        let timeout_reply = TimeoutReply::new(ctx, invocation.path, command, settled.clone());
+        let watch = self.watchdog.watch(command.timeout(), move || timeout_reply.send());
+        let exec = || command.exec(ctx, args);
+        let result = match panic::catch_unwind(AssertUnwindSafe(exec)) {
+            Ok(r) => r,
+            Err(payload) => Err(CommandError::Internal(panic_message(payload))),
+        };
+        let finished_in_time = !settled.swap(true, Ordering::SeqCst);
+        drop(watch);
+
+        if !finished_in_time {
+            return Err(CommandError::TimedOut);
+        }
+        result
+    }
+}
+
+/// What to do when a command takes too long: stop it sending anything else,
+/// tell the user, and if it's running in a worker pool, start another worker
+/// so the stuck one doesn't hold up others.
+struct TimeoutReply {
+    responder: Arc<Responder>,
+    rate_limiter: Arc<RateLimiter>,
+    replies: Arc<Replies>,
+    /// Set by whichever of the command and the timeout comes first.
+    settled: Arc<AtomicBool>,
+    timed_out: Arc<AtomicBool>,
+    channel: ChannelId,
+    path: String,
+    text: String,
+    pool: Option<PoolHandle>,
+    log_fields: Vec<(&'static str, String)>,
+}
+
+impl TimeoutReply {
+    fn new(ctx: &Context,
+           path: &str,
+           command: &Command,
+           settled: Arc<AtomicBool>)
+           -> TimeoutReply {
+        TimeoutReply {
+            responder: ctx.responder.clone(),
+            rate_limiter: ctx.dispatcher.rate_limiter.clone(),
+            replies: ctx.replies.clone(),
+            settled: settled,
+            timed_out: ctx.timed_out.clone(),
+            channel: ctx.message.channel_id,
+            path: path.to_string(),
+            text: error_reply(ctx.prefix, path, command, &CommandError::TimedOut),
+            pool: PoolHandle::current(),
+            log_fields: logging::current_fields(),
+        }
+    }
+
+    fn send(self) {
+        // The command finished just in time.
+        if self.settled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.timed_out.store(true, Ordering::SeqCst);
+        let _fields = logging::scope(self.log_fields);
+        warn!("{} is taking too long; telling the user it was cancelled", self.path);
+        if let Err(e) = command::send_text(&*self.responder,
+                                           &self.rate_limiter,
+                                           &self.replies,
+                                           self.channel,
+                                           &self.text) {
+            warn!("Unable to reply in channel {}: {}", self.channel, e);
+        }
+        if let Some(pool) = self.pool {
+            pool.replace_worker();
+        }
+    }
+}
+
+/// The command in `content`, after the prefix or a mention of `bot`, and
+/// whether it was a mention.
+fn command_text<'a>(content: &'a str, prefix: &str, bot: UserId) -> Option<(&'a str, bool)> {
+    match strip_mention(content, bot) {
+        Some(rest) => Some((rest, true)),
+        None if content.starts_with(prefix) => Some((&content[prefix.len()..], false)),
+        None => None,
+    }
+}
+
+/// The rest of `content` when it starts by mentioning `user`.
+fn strip_mention(content: &str, user: UserId) -> Option<&str> {
+    let mentions = [format!("<@{}>", user.0), format!("<@!{}>", user.0)];
+    for mention in mentions.iter() {
+        if content.starts_with(mention.as_str()) {
+            return Some(content[mention.len()..].trim_left());
+        }
+    }
+
+    None
+}
+
+/// Where the message came from, for every line logged while handling it.
+fn message_fields(message: &IncomingMessage) -> Vec<(&'static str, String)> {
+    let mut fields = Vec::new();
+    if let Some(server) = message.server_id {
+        fields.push(("guild", server.0.to_string()));
+    }
+    fields.push(("channel", message.channel_id.0.to_string()));
+    fields.push(("user", message.author.id.0.to_string()));
+    fields
+}
+
+fn error_reply(prefix: &str, path: &str, command: &Command, error: &CommandError) -> String {
+    match error {
+        &CommandError::BadArguments(ref m) => with_usage(m, prefix, path, command),
+        &CommandError::Arguments(ref e) => {
+            let reply = with_usage(&e.to_string(), prefix, path, command);
+            match e.excerpt() {
+                Some(excerpt) => format!("{}\n{}", reply, excerpt),
+                None => reply,
+            }
+        }
+        &CommandError::Upstream(_) => {
+            format!("`{}{}` couldn't get what it needed. Try again later.",
+                    prefix,
+                    path)
+        }
+        &CommandError::PermissionDenied(ref m) => {
+            format!("You can't use `{}{}`: {}", prefix, path, m)
+        }
+        &CommandError::Internal(_) => format!("Something went wrong running `{}{}`.", prefix, path),
+        &CommandError::TimedOut => {
+            format!("`{}{}` took too long and was cancelled. Try again later.",
+                    prefix,
+                    path)
+        }
+        &CommandError::CoolingDown(wait) => {
+            let seconds = command::wait_seconds(wait);
+            format!("Slow down! You can use `{}{}` again in {} second{}.",
+                    prefix,
+                    path,
+                    seconds,
+                    if seconds == 1 { "" } else { "s" })
+        }
+    }
+}
+
+/// `message` followed by how the command is used, if it takes arguments.
+fn with_usage(message: &str, prefix: &str, path: &str, command: &Command) -> String {
+    match command::usage(command) {
+        Some(usage) => format!("{} Usage: `{}{} {}`", message, prefix, path, usage),
+        None => message.to_string(),
+    }
+}
+
+fn reply(ctx: &Context, text: &str) {
+    if let Err(e) = ctx.send_message(text) {
+        warn!("Unable to reply in channel {}: {}", ctx.message.channel_id, e);
+    }
+}
+
+fn panic_message(payload: Box<Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        return format!("panicked: {}", s);
+    }
+    if let Some(s) = payload.downcast_ref::<String>() {
+        return format!("panicked: {}", s);
+    }
+
+    "panicked".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use discord::model::MessageId;
+    use framework::cooldown::{Bucket, Cooldown};
+    use framework::middleware;
+    use framework::mock::{MockResponder, Sent};
+    use framework::transport::Author;
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
+
+    fn message(content: &str) -> IncomingMessage {
+        IncomingMessage {
+            id: MessageId(1),
+            channel_id: ChannelId(2),
+            server_id: None,
+            author: Author {
+                id: UserId(3),
+                name: "someone".to_string(),
+            },
+            member: None,
+            directory: None,
+            content: content.to_string(),
+        }
+    }
+
+    struct Slow;
+
+    impl Command for Slow {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        fn timeout(&self) -> Duration {
+            Duration::from_millis(20)
+        }
+
+        fn exec(&self, ctx: &Context, _args: Args) -> command::CommandResult {
+            thread::sleep(Duration::from_millis(200));
+            ctx.send_message("Done")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn timed_out_command_is_cancelled() {
+        let mut registry = CommandRegistry::new();
+        registry.register(Slow);
+        let dispatcher = Dispatcher::new(registry, Prefixes::new("!"), vec![]);
+        let responder = Arc::new(MockResponder::new(UserId(1)));
+
+        dispatcher.dispatch(responder.clone(), &message("!slow"));
+
+        assert_eq!(responder.take_sent(),
+                   vec![Sent::Message {
+                            channel: ChannelId(2),
+                            text: "`!slow` took too long and was cancelled. Try again later."
+                                .to_string(),
+                        }]);
+    }
+
+    #[test]
+    fn editing_reuses_the_timeout_notice() {
+        let mut registry = CommandRegistry::new();
+        registry.register(Slow);
+        registry.register(Hello);
+        let dispatcher = Dispatcher::new(registry, Prefixes::new("!"), vec![]);
+        let responder = Arc::new(MockResponder::new(UserId(1)));
+        dispatcher.dispatch(responder.clone(), &message("!slow"));
+        responder.take_sent();
+
+        dispatcher.dispatch_edit(responder.clone(), &message("!hello"));
+
+        assert_eq!(responder.take_sent(),
+                   vec![Sent::Edit {
+                            channel: ChannelId(2),
+                            message: MessageId(1),
+                            text: "Hello!".to_string(),
+                        }]);
+    }
+
+    struct Hello;
+
+    impl Command for Hello {
+        fn name(&self) -> &'static str {
+            "hello"
+        }
+
+        fn cooldown(&self) -> Option<Cooldown> {
+            Some(Cooldown {
+                     bucket: Bucket::User,
+                     uses: 1,
+                     per: Duration::from_secs(60),
+                 })
+        }
+
+        fn exec(&self, ctx: &Context, _args: Args) -> command::CommandResult {
+            ctx.send_message("Hello!")?;
+            Ok(())
+        }
+    }
+
+    /// Says each word of its arguments in a message of its own.
+    struct Echo;
+
+    impl Command for Echo {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn exec(&self, ctx: &Context, args: Args) -> command::CommandResult {
+            for word in args.rest().split_whitespace() {
+                ctx.send_message(word)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn edits_delete_replies_no_longer_needed() {
+        let mut registry = CommandRegistry::new();
+        registry.register(Echo);
+        let dispatcher = Dispatcher::new(registry, Prefixes::new("!"), vec![]);
+        let responder = Arc::new(MockResponder::new(UserId(1)));
+        dispatcher.dispatch(responder.clone(), &message("!echo a b c"));
+        responder.take_sent();
+
+        dispatcher.dispatch_edit(responder.clone(), &message("!echo d"));
+
+        assert_eq!(responder.take_sent(),
+                   vec![Sent::Edit {
+                            channel: ChannelId(2),
+                            message: MessageId(1),
+                            text: "d".to_string(),
+                        },
+                        Sent::Delete {
+                            channel: ChannelId(2),
+                            message: MessageId(2),
+                        },
+                        Sent::Delete {
+                            channel: ChannelId(2),
+                            message: MessageId(3),
+                        }]);
+    }
+
+    /// Notes each hook it sees, and stops commands given `no` as arguments.
+    struct Record {
+        name: &'static str,
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Record {
+        fn before(&self, _ctx: &Context, invocation: &Invocation) -> CommandResult {
+            self.seen.lock().unwrap().push(format!("{} before {}", self.name, invocation.path));
+            if invocation.args == "no" {
+                return Err(CommandError::PermissionDenied("Not today.".to_string()));
+            }
+            Ok(())
+        }
+
+        fn after(&self,
+                 _ctx: &Context,
+                 _invocation: &Invocation,
+                 result: &CommandResult,
+                 _duration: Duration) {
+            let outcome = middleware::outcome(result);
+            self.seen.lock().unwrap().push(format!("{} after {}", self.name, outcome));
+        }
+    }
+
+    fn recording_dispatcher(seen: &Arc<Mutex<Vec<String>>>) -> Dispatcher {
+        let mut registry = CommandRegistry::new();
+        registry.register(Hello);
+        let mut dispatcher = Dispatcher::new(registry, Prefixes::new("!"), vec![]);
+        dispatcher.add_middleware(Record {
+                                      name: "outer",
+                                      seen: seen.clone(),
+                                  });
+        dispatcher.add_middleware(Record {
+                                      name: "inner",
+                                      seen: seen.clone(),
+                                  });
+        dispatcher
+    }
+
+    #[test]
+    fn middleware_wraps_commands() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let dispatcher = recording_dispatcher(&seen);
+        let responder = Arc::new(MockResponder::new(UserId(1)));
+
+        dispatcher.dispatch(responder.clone(), &message("!hello"));
+
+        assert_eq!(*seen.lock().unwrap(),
+                   vec!["outer before hello",
+                        "inner before hello",
+                        "inner after ok",
+                        "outer after ok"]);
+        assert_eq!(responder.take_sent().len(), 1);
+        assert_eq!(dispatcher.metrics().commands_run("hello", "ok"), 1);
+    }
+
+    #[test]
+    fn middleware_can_stop_commands() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let dispatcher = recording_dispatcher(&seen);
+        let responder = Arc::new(MockResponder::new(UserId(1)));
+
+        dispatcher.dispatch(responder.clone(), &message("!hello no"));
+
+        assert_eq!(*seen.lock().unwrap(),
+                   vec!["outer before hello",
+                        "inner after permission_denied",
+                        "outer after permission_denied"]);
+        assert_eq!(responder.take_sent(),
+                   vec![Sent::Message {
+                            channel: ChannelId(2),
+                            text: "You can't use `!hello`: Not today.".to_string(),
+                        }]);
+        assert_eq!(dispatcher.metrics().commands_run("hello", "permission_denied"), 1);
+    }
+
+    #[test]
+    fn cooldown_stops_commands() {
+        let mut registry = CommandRegistry::new();
+        registry.register(Hello);
+        let dispatcher = Dispatcher::new(registry, Prefixes::new("!"), vec![]);
+        let responder = Arc::new(MockResponder::new(UserId(1)));
+
+        dispatcher.dispatch(responder.clone(), &message("!hello"));
+        dispatcher.dispatch(responder.clone(), &message("!hello"));
+
+        assert_eq!(responder.take_sent()[1],
+                   Sent::Message {
+                       channel: ChannelId(2),
+                       text: "Slow down! You can use `!hello` again in 60 seconds.".to_string(),
+                   });
+        assert_eq!(dispatcher.metrics().commands_run("hello", "cooldown"), 1);
+    }
+
+    #[test]
+    fn strip_mention_forms() {
+        assert_eq!(strip_mention("<@42> ping", UserId(42)), Some("ping"));
+        assert_eq!(strip_mention("<@!42>   ffxiv events", UserId(42)),
+                   Some("ffxiv events"));
+        assert_eq!(strip_mention("<@42>", UserId(42)), Some(""));
+    }
+
+    #[test]
+    fn strip_mention_other_user() {
+        assert_eq!(strip_mention("<@43> ping", UserId(42)), None);
+        assert_eq!(strip_mention("<@421> ping", UserId(42)), None);
+        assert_eq!(strip_mention("!ping", UserId(42)), None);
+    }
+
+    #[test]
+    fn addressed_by_prefix_or_mention() {
+        let dispatcher = Dispatcher::new(CommandRegistry::new(), Prefixes::new("!"), vec![]);
+
+        assert!(dispatcher.is_addressed(&message("!anything"), UserId(42)));
+        assert!(dispatcher.is_addressed(&message("<@42> hi"), UserId(42)));
+        assert!(!dispatcher.is_addressed(&message("just chatting"), UserId(42)));
+        assert!(!dispatcher.is_addressed(&message("<@43> hi"), UserId(42)));
+    }
+}
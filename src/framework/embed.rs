@@ -0,0 +1,45 @@
+/// A rich message, independent of how it ends up being sent.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Embed {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub fields: Vec<EmbedField>,
+    pub footer: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmbedField {
+    pub name: String,
+    pub value: String,
+    pub inline: bool,
+}
+
+impl Embed {
+    pub fn new() -> Embed {
+        Embed::default()
+    }
+
+    pub fn title(mut self, title: &str) -> Embed {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Embed {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn field(mut self, name: &str, value: &str, inline: bool) -> Embed {
+        self.fields.push(EmbedField {
+                             name: name.to_string(),
+                             value: value.to_string(),
+                             inline: inline,
+                         });
+        self
+    }
+
+    pub fn footer(mut self, footer: &str) -> Embed {
+        self.footer = Some(footer.to_string());
+        self
+    }
+}
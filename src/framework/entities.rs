@@ -0,0 +1,339 @@
+//! Arguments that refer to users, channels, roles and emoji in a server.
+//!
+//! Each can be given with Discord's mention syntax (`<@123>`, `<#456>`,
+//! `<@&789>`, `<:name:012>`), as a bare ID, or by name. Names are looked up in
+//! the server the command was sent in, loosest match last: exactly as given,
+//! then ignoring case, then as the start of a name, then anywhere in a name,
+//! then allowing for a typo. The first of those to match only one thing wins,
+//! and one that matches several asks the user to be more specific.
+
+use discord::model::{ChannelId, EmojiId, LiveServer, RoleId, UserId};
+use std::cmp;
+use util::edit_distance::levenshtein;
+
+/// How many of the things an ambiguous name matched to list.
+const MAX_AMBIGUOUS: usize = 5;
+
+/// The names of everything in a server that arguments can refer to, taken
+/// when a command arrives so commands don't need the live state.
+#[derive(Clone, Debug, Default)]
+pub struct Directory {
+    pub users: Vec<Entry<UserId>>,
+    pub channels: Vec<Entry<ChannelId>>,
+    pub roles: Vec<Entry<RoleId>>,
+    pub emoji: Vec<Entry<EmojiId>>,
+}
+
+impl Directory {
+    pub fn new(server: &LiveServer) -> Directory {
+        Directory {
+            users: server.members
+                .iter()
+                .map(|m| {
+                    let mut names = vec![m.user.name.clone()];
+                    if let Some(ref nick) = m.nick {
+                        names.insert(0, nick.clone());
+                    }
+                    Entry::new(m.user.id, names)
+                })
+                .collect(),
+            channels: server.channels
+                .iter()
+                .map(|c| Entry::new(c.id, vec![c.name.clone()]))
+                .collect(),
+            roles: server.roles.iter().map(|r| Entry::new(r.id, vec![r.name.clone()])).collect(),
+            emoji: server.emojis.iter().map(|e| Entry::new(e.id, vec![e.name.clone()])).collect(),
+        }
+    }
+}
+
+/// Something in a server, and the names it goes by.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry<T> {
+    pub id: T,
+    /// The name to show first, then any others it can be found by, like a
+    /// user's username after their nickname.
+    pub names: Vec<String>,
+}
+
+impl<T> Entry<T> {
+    pub fn new(id: T, names: Vec<String>) -> Entry<T> {
+        Entry {
+            id: id,
+            names: names,
+        }
+    }
+}
+
+/// A kind of thing in a server that arguments can refer to.
+pub trait Entity: Copy + PartialEq {
+    /// What it's called in messages, like "user".
+    fn kind() -> &'static str;
+
+    fn from_id(id: u64) -> Self;
+
+    /// The ID in `text`, if it's a mention of this kind of thing.
+    fn mentioned(text: &str) -> Option<u64>;
+
+    /// `text` without any decoration conventional for names of this kind, like
+    /// the `#` in front of a channel name.
+    fn bare_name(text: &str) -> &str {
+        text
+    }
+
+    fn entries(directory: &Directory) -> &[Entry<Self>];
+}
+
+impl Entity for UserId {
+    fn kind() -> &'static str {
+        "user"
+    }
+
+    fn from_id(id: u64) -> UserId {
+        UserId(id)
+    }
+
+    /// `<@123>`, or `<@!123>` for users with a nickname.
+    fn mentioned(text: &str) -> Option<u64> {
+        between(text, "<@!").or_else(|| between(text, "<@"))
+    }
+
+    fn bare_name(text: &str) -> &str {
+        trim_prefix(text, "@")
+    }
+
+    fn entries(directory: &Directory) -> &[Entry<UserId>] {
+        &directory.users
+    }
+}
+
+impl Entity for ChannelId {
+    fn kind() -> &'static str {
+        "channel"
+    }
+
+    fn from_id(id: u64) -> ChannelId {
+        ChannelId(id)
+    }
+
+    fn mentioned(text: &str) -> Option<u64> {
+        between(text, "<#")
+    }
+
+    fn bare_name(text: &str) -> &str {
+        trim_prefix(text, "#")
+    }
+
+    fn entries(directory: &Directory) -> &[Entry<ChannelId>] {
+        &directory.channels
+    }
+}
+
+impl Entity for RoleId {
+    fn kind() -> &'static str {
+        "role"
+    }
+
+    fn from_id(id: u64) -> RoleId {
+        RoleId(id)
+    }
+
+    fn mentioned(text: &str) -> Option<u64> {
+        between(text, "<@&")
+    }
+
+    fn bare_name(text: &str) -> &str {
+        trim_prefix(text, "@")
+    }
+
+    fn entries(directory: &Directory) -> &[Entry<RoleId>] {
+        &directory.roles
+    }
+}
+
+impl Entity for EmojiId {
+    fn kind() -> &'static str {
+        "emoji"
+    }
+
+    fn from_id(id: u64) -> EmojiId {
+        EmojiId(id)
+    }
+
+    /// `<:name:123>`, or `<a:name:123>` for animated emoji.
+    fn mentioned(text: &str) -> Option<u64> {
+        if !(text.starts_with("<:") || text.starts_with("<a:")) || !text.ends_with('>') {
+            return None;
+        }
+        text[..text.len() - 1].rsplit(':').next().and_then(|id| id.parse().ok())
+    }
+
+    /// Emoji are usually typed as `:name:`.
+    fn bare_name(text: &str) -> &str {
+        text.trim_matches(':')
+    }
+
+    fn entries(directory: &Directory) -> &[Entry<EmojiId>] {
+        &directory.emoji
+    }
+}
+
+/// The ID between `open` and `>` in `text`.
+fn between(text: &str, open: &str) -> Option<u64> {
+    if text.starts_with(open) && text.ends_with('>') && text.len() > open.len() {
+        text[open.len()..text.len() - 1].parse().ok()
+    } else {
+        None
+    }
+}
+
+fn trim_prefix<'a>(text: &'a str, prefix: &str) -> &'a str {
+    if text.starts_with(prefix) {
+        &text[prefix.len()..]
+    } else {
+        text
+    }
+}
+
+/// What `text` refers to: a mention, an ID or a name in `directory`, the
+/// server the command was sent in. Without a server, only mentions work. On
+/// failure, says why in a form fit to show the user.
+pub fn resolve<T: Entity>(text: &str, directory: Option<&Directory>) -> Result<T, String> {
+    if let Some(id) = T::mentioned(text) {
+        return Ok(T::from_id(id));
+    }
+    let directory = match directory {
+        Some(directory) => directory,
+        None => return Err(format!("Outside a server, give a {} as a mention.", T::kind())),
+    };
+    let entries = T::entries(directory);
+
+    if let Ok(id) = text.parse::<u64>() {
+        let id = T::from_id(id);
+        if entries.iter().any(|e| e.id == id) {
+            return Ok(id);
+        }
+    }
+
+    let name = T::bare_name(text);
+    let wanted = name.to_lowercase();
+    if wanted.is_empty() {
+        return Err(format!("There's no {} named `{}`.", T::kind(), text));
+    }
+    let stages: [&Fn(&str) -> bool; 4] = [&|n| n == name,
+                                          &|n| n.to_lowercase() == wanted,
+                                          &|n| n.to_lowercase().starts_with(&wanted),
+                                          &|n| n.to_lowercase().contains(&wanted)];
+    for matches in &stages {
+        let found: Vec<&Entry<T>> =
+            entries.iter().filter(|e| e.names.iter().any(|n| matches(n))).collect();
+        if !found.is_empty() {
+            return one_of(text, &found);
+        }
+    }
+
+    // Last of all, allow for a typo or two, depending on the length.
+    let allowed = cmp::max(1, wanted.chars().count() / 3);
+    let distance = |e: &Entry<T>| {
+        e.names.iter().map(|n| levenshtein(&n.to_lowercase(), &wanted)).min().unwrap_or(allowed + 1)
+    };
+    let closest = entries.iter().map(&distance).min().unwrap_or(allowed + 1);
+    if closest <= allowed {
+        let found: Vec<&Entry<T>> = entries.iter().filter(|e| distance(e) == closest).collect();
+        return one_of(text, &found);
+    }
+
+    Err(format!("There's no {} named `{}`.", T::kind(), text))
+}
+
+/// The only thing in `found`, or a message listing them if there are several.
+fn one_of<T: Entity>(text: &str, found: &[&Entry<T>]) -> Result<T, String> {
+    if found.len() == 1 {
+        return Ok(found[0].id);
+    }
+
+    let mut names: Vec<String> = found.iter()
+        .take(MAX_AMBIGUOUS)
+        .map(|e| format!("`{}`", e.names[0]))
+        .collect();
+    if found.len() > MAX_AMBIGUOUS {
+        names.push(format!("{} more", found.len() - MAX_AMBIGUOUS));
+    }
+    Err(format!("`{}` could be {}. Try a mention instead.", text, names.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn directory() -> Directory {
+        Directory {
+            users: vec![Entry::new(UserId(1), names(&["Tech", "technobot"])),
+                        Entry::new(UserId(2), names(&["Alice"])),
+                        Entry::new(UserId(3), names(&["alan"])),
+                        Entry::new(UserId(4), names(&["Al"]))],
+            channels: vec![Entry::new(ChannelId(10), names(&["general"])),
+                           Entry::new(ChannelId(11), names(&["ffxiv-raids"]))],
+            roles: vec![Entry::new(RoleId(20), names(&["Moderator"]))],
+            emoji: vec![Entry::new(EmojiId(30), names(&["moogle"]))],
+        }
+    }
+
+    fn user(text: &str) -> Result<UserId, String> {
+        resolve(text, Some(&directory()))
+    }
+
+    #[test]
+    fn mentions() {
+        let directory = directory();
+
+        assert_eq!(resolve("<@5>", Some(&directory)), Ok(UserId(5)));
+        assert_eq!(resolve("<@!5>", Some(&directory)), Ok(UserId(5)));
+        assert_eq!(resolve("<#11>", Some(&directory)), Ok(ChannelId(11)));
+        assert_eq!(resolve("<@&20>", Some(&directory)), Ok(RoleId(20)));
+        assert_eq!(resolve("<:moogle:30>", Some(&directory)), Ok(EmojiId(30)));
+        assert_eq!(resolve("<a:dance:31>", None), Ok(EmojiId(31)));
+        assert!(resolve::<UserId>("<@&20>", Some(&directory)).is_err());
+    }
+
+    #[test]
+    fn ids_in_the_server() {
+        assert_eq!(user("2"), Ok(UserId(2)));
+        assert!(user("9").is_err());
+    }
+
+    #[test]
+    fn names_loosest_last() {
+        assert_eq!(user("Al"), Ok(UserId(4)));
+        assert_eq!(user("al"), Ok(UserId(4)));
+        assert_eq!(user("ALI"), Ok(UserId(2)));
+        assert_eq!(user("technobot"), Ok(UserId(1)));
+        assert_eq!(user("@lan"), Ok(UserId(3)));
+        assert_eq!(user("Alics"), Ok(UserId(2)));
+
+        let directory = directory();
+        assert_eq!(resolve("#raids", Some(&directory)), Ok(ChannelId(11)));
+        assert_eq!(resolve("@mod", Some(&directory)), Ok(RoleId(20)));
+        assert_eq!(resolve(":moogle:", Some(&directory)), Ok(EmojiId(30)));
+    }
+
+    #[test]
+    fn ambiguous_names() {
+        let mut directory = directory();
+        directory.users.push(Entry::new(UserId(5), names(&["Alicia"])));
+
+        assert_eq!(resolve::<UserId>("ali", Some(&directory)),
+                   Err("`ali` could be `Alice`, `Alicia`. Try a mention instead.".to_string()));
+    }
+
+    #[test]
+    fn unknown_names() {
+        assert_eq!(user("zed"), Err("There's no user named `zed`.".to_string()));
+        assert_eq!(user("@"), Err("There's no user named `@`.".to_string()));
+        assert!(resolve::<ChannelId>("general", None).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use framework::command::Command;
+use framework::registry::{CommandRegistry, Entry};
+
+/// A named set of subcommands, invoked as `<group> <subcommand>`.
+pub struct CommandGroup {
+    name: &'static str,
+    description: Option<&'static str>,
+    default: Option<&'static str>,
+    commands: CommandRegistry,
+}
+
+impl CommandGroup {
+    pub fn new(name: &'static str, description: &'static str) -> CommandGroup {
+        CommandGroup {
+            name: name,
+            description: Some(description),
+            default: None,
+            commands: CommandRegistry::new(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn description(&self) -> Option<&'static str> {
+        self.description
+    }
+
+    pub fn register<C: Command + 'static>(&mut self, command: C) {
+        self.commands.register(command);
+    }
+
+    pub fn register_group(&mut self, group: CommandGroup) {
+        self.commands.register_group(group);
+    }
+
+    /// Run the subcommand named `name`, or with the alias `name`, when the
+    /// group is invoked without one.
+    pub fn set_default(&mut self, name: &'static str) {
+        self.default = Some(name);
+    }
+
+    pub fn default_command(&self) -> Option<&Command> {
+        let default = match self.default {
+            Some(d) => d,
+            None => return None,
+        };
+
+        for entry in self.commands.entries() {
+            if let &Entry::Command(ref cmd) = entry {
+                if cmd.name() == default || cmd.aliases().contains(&default) {
+                    return Some(cmd.as_ref());
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn commands(&self) -> &CommandRegistry {
+        &self.commands
+    }
+}
@@ -0,0 +1,85 @@
+use framework::command::{Command, CommandError, CommandResult, Context};
+use logging;
+use metrics::Metrics;
+use std::sync::Arc;
+use std::time::Duration;
+use util::duration_ms;
+
+/// The command a message invoked.
+pub struct Invocation<'a> {
+    /// The command's full name, e.g. `ffxiv resets`.
+    pub path: &'a str,
+    pub command: &'a Command,
+    /// The message after the command name.
+    pub args: &'a str,
+}
+
+/// Behavior wrapped around every command the dispatcher runs, like checking
+/// permissions or counting uses.
+///
+/// Before hooks run in the order middleware was added, and the first to
+/// return an error stops the command running; the error is reported to the
+/// user as if the command had returned it. After hooks run in the opposite
+/// order, for every invocation, including ones a before hook stopped.
+pub trait Middleware: Send + Sync {
+    fn before(&self, _ctx: &Context, _invocation: &Invocation) -> CommandResult {
+        Ok(())
+    }
+
+    /// `result` is what the command returned, or the error that stopped it.
+    /// `duration` includes the before hooks.
+    fn after(&self,
+             _ctx: &Context,
+             _invocation: &Invocation,
+             _result: &CommandResult,
+             _duration: Duration) {
+    }
+}
+
+/// The outcome recorded for `result`: `"ok"`, or the kind of error.
+pub fn outcome(result: &CommandResult) -> &'static str {
+    match result {
+        &Ok(()) => "ok",
+        &Err(ref e) => e.kind(),
+    }
+}
+
+/// Logs each command run, with how it went and how long it took.
+pub struct LogCommands;
+
+impl Middleware for LogCommands {
+    fn after(&self,
+             _ctx: &Context,
+             invocation: &Invocation,
+             result: &CommandResult,
+             duration: Duration) {
+        let path = invocation.path;
+        if let &Err(ref error) = result {
+            // Bugs are errors and failures outside the bot are warnings. The
+            // rest are the user's mistakes, and only interesting when debugging.
+            match error {
+                &CommandError::Internal(_) => error!("Error running {}: {}", path, error),
+                &CommandError::Upstream(_) |
+                &CommandError::TimedOut => warn!("Error running {}: {}", path, error),
+                _ => debug!("Error running {}: {}", path, error),
+            }
+        }
+
+        let _fields = logging::scope(vec![("outcome", outcome(result).to_string()),
+                                          ("latency_ms", duration_ms(duration).to_string())]);
+        info!("Ran {}", path);
+    }
+}
+
+/// Counts each command run in `Metrics`.
+pub struct RecordMetrics(pub Arc<Metrics>);
+
+impl Middleware for RecordMetrics {
+    fn after(&self,
+             _ctx: &Context,
+             invocation: &Invocation,
+             result: &CommandResult,
+             duration: Duration) {
+        self.0.record_command(invocation.path, outcome(result), duration);
+    }
+}
@@ -0,0 +1,124 @@
+use discord::model::{ChannelId, MessageId, UserId};
+use framework::command::{CommandError, CommandResult};
+use framework::embed::Embed;
+use framework::transport::Responder;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Something a `MockResponder` was asked to send.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sent {
+    Message { channel: ChannelId, text: String },
+    Embed { channel: ChannelId, embed: Embed },
+    Edit {
+        channel: ChannelId,
+        message: MessageId,
+        text: String,
+    },
+    EmbedEdit {
+        channel: ChannelId,
+        message: MessageId,
+        embed: Embed,
+    },
+    Delete {
+        channel: ChannelId,
+        message: MessageId,
+    },
+    Reaction {
+        channel: ChannelId,
+        message: MessageId,
+        emoji: String,
+    },
+    PrivateMessage { user: UserId, text: String },
+}
+
+/// Records everything sent through it instead of talking to a chat service.
+pub struct MockResponder {
+    user_id: UserId,
+    sent: Mutex<Vec<Sent>>,
+    /// Every message sent, including those already taken, so IDs stay unique.
+    messages: AtomicUsize,
+}
+
+impl MockResponder {
+    pub fn new(user_id: UserId) -> MockResponder {
+        MockResponder {
+            user_id: user_id,
+            sent: Mutex::new(Vec::new()),
+            messages: AtomicUsize::new(0),
+        }
+    }
+
+    /// Everything sent since the last call.
+    pub fn take_sent(&self) -> Vec<Sent> {
+        let mut sent = self.sent.lock().unwrap();
+        sent.drain(..).collect()
+    }
+
+    fn record(&self, sent: Sent) -> MessageId {
+        self.sent.lock().unwrap().push(sent);
+        MessageId(self.messages.fetch_add(1, Ordering::SeqCst) as u64 + 1)
+    }
+}
+
+impl Responder for MockResponder {
+    fn user_id(&self) -> UserId {
+        self.user_id
+    }
+
+    fn send_message(&self, channel: ChannelId, text: &str) -> Result<MessageId, CommandError> {
+        Ok(self.record(Sent::Message {
+                           channel: channel,
+                           text: text.to_string(),
+                       }))
+    }
+
+    fn send_embed(&self, channel: ChannelId, embed: &Embed) -> Result<MessageId, CommandError> {
+        Ok(self.record(Sent::Embed {
+                           channel: channel,
+                           embed: embed.clone(),
+                       }))
+    }
+
+    fn edit_message(&self, channel: ChannelId, message: MessageId, text: &str) -> CommandResult {
+        self.sent.lock().unwrap().push(Sent::Edit {
+                                           channel: channel,
+                                           message: message,
+                                           text: text.to_string(),
+                                       });
+        Ok(())
+    }
+
+    fn edit_embed(&self, channel: ChannelId, message: MessageId, embed: &Embed) -> CommandResult {
+        self.sent.lock().unwrap().push(Sent::EmbedEdit {
+                                           channel: channel,
+                                           message: message,
+                                           embed: embed.clone(),
+                                       });
+        Ok(())
+    }
+
+    fn delete_message(&self, channel: ChannelId, message: MessageId) -> CommandResult {
+        self.sent.lock().unwrap().push(Sent::Delete {
+                                           channel: channel,
+                                           message: message,
+                                       });
+        Ok(())
+    }
+
+    fn react(&self, channel: ChannelId, message: MessageId, emoji: &str) -> Result<(), CommandError> {
+        self.record(Sent::Reaction {
+                        channel: channel,
+                        message: message,
+                        emoji: emoji.to_string(),
+                    });
+        Ok(())
+    }
+
+    fn send_private_message(&self, user: UserId, text: &str) -> Result<MessageId, CommandError> {
+        Ok(self.record(Sent::PrivateMessage {
+                           user: user,
+                           text: text.to_string(),
+                       }))
+    }
+}
@@ -1 +1,19 @@
 pub mod args;
+pub mod command;
+pub mod console;
+pub mod cooldown;
+pub mod dispatcher;
+pub mod embed;
+pub mod entities;
+pub mod group;
+pub mod middleware;
+pub mod mock;
+pub mod permissions;
+pub mod pool;
+pub mod prefixes;
+pub mod ratelimit;
+pub mod registry;
+pub mod replies;
+pub mod shutdown;
+pub mod transport;
+pub mod watchdog;
@@ -0,0 +1,241 @@
+use discord::model::{ChannelId, LiveServer, Permissions, UserId, permissions};
+use framework::command::{CommandError, CommandResult, Context};
+use framework::middleware::{Invocation, Middleware};
+use framework::transport::IncomingMessage;
+use std::fmt;
+
+/// Something a user must have to run a command.
+pub enum Permission {
+    /// One of the bot's owners.
+    Owner,
+    /// The server owner, or anyone with the Administrator permission.
+    ServerAdmin,
+    /// All of these permissions in the channel the command was sent in.
+    Discord(Permissions),
+    /// A role with this name, compared case-insensitively.
+    Role(&'static str),
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Permission::Owner => write!(f, "Bot owner"),
+            &Permission::ServerAdmin => write!(f, "Server administrator"),
+            &Permission::Discord(p) => write!(f, "Discord permissions {:#x}", p.bits()),
+            &Permission::Role(name) => write!(f, "The `{}` role", name),
+        }
+    }
+}
+
+/// What the user running a command is allowed to do where they ran it.
+pub struct Caller {
+    pub is_owner: bool,
+    /// `None` when the command was sent in a private channel.
+    pub server: Option<ServerCaller>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerCaller {
+    pub is_server_owner: bool,
+    pub permissions: Permissions,
+    pub role_names: Vec<String>,
+}
+
+impl ServerCaller {
+    /// What `user` may do in `channel` of `server`.
+    pub fn new(server: &LiveServer, channel: ChannelId, user: UserId) -> ServerCaller {
+        let role_ids = server.members
+            .iter()
+            .find(|m| m.user.id == user)
+            .map(|m| m.roles.clone())
+            .unwrap_or_default();
+        let role_names = server.roles
+            .iter()
+            .filter(|r| role_ids.contains(&r.id))
+            .map(|r| r.name.clone())
+            .collect();
+
+        ServerCaller {
+            is_server_owner: server.owner_id == user,
+            permissions: server.permissions_for(channel, user),
+            role_names: role_names,
+        }
+    }
+}
+
+impl Caller {
+    pub fn new(owners: &[UserId], message: &IncomingMessage) -> Caller {
+        Caller {
+            is_owner: owners.contains(&message.author.id),
+            server: message.member.clone(),
+        }
+    }
+
+    /// Whether the caller has every permission in `required`. Owners always
+    /// do. On failure, says what is missing in a form fit to show the user.
+    pub fn check(&self, required: &[Permission]) -> Result<(), String> {
+        if self.is_owner {
+            return Ok(());
+        }
+
+        for permission in required {
+            let missing = match (permission, self.server.as_ref()) {
+                (&Permission::Owner, _) => Some("Only the bot's owners can do that.".to_string()),
+                (_, None) => Some("That only works in a server.".to_string()),
+                (&Permission::ServerAdmin, Some(server)) => {
+                    if server.is_server_owner ||
+                       server.permissions.contains(permissions::ADMINISTRATOR) {
+                        None
+                    } else {
+                        Some("You need to be a server administrator.".to_string())
+                    }
+                }
+                (&Permission::Discord(p), Some(server)) => {
+                    if server.permissions.contains(p) {
+                        None
+                    } else {
+                        Some("You don't have the Discord permissions that needs.".to_string())
+                    }
+                }
+                (&Permission::Role(name), Some(server)) => {
+                    let lowercase_name = name.to_lowercase();
+                    if server.role_names.iter().any(|r| r.to_lowercase() == lowercase_name) {
+                        None
+                    } else {
+                        Some(format!("You need the `{}` role.", name))
+                    }
+                }
+            };
+
+            if let Some(m) = missing {
+                return Err(m);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Stops commands running for users without their `Command::permissions`.
+pub struct CheckPermissions {
+    owners: Vec<UserId>,
+}
+
+impl CheckPermissions {
+    pub fn new(owners: Vec<UserId>) -> CheckPermissions {
+        CheckPermissions { owners: owners }
+    }
+}
+
+impl Middleware for CheckPermissions {
+    fn before(&self, ctx: &Context, invocation: &Invocation) -> CommandResult {
+        Caller::new(&self.owners, ctx.message)
+            .check(invocation.command.permissions())
+            .map_err(CommandError::PermissionDenied)
+    }
+}
+
+/// Parse a comma separated list of user IDs, as used for the list of owners.
+pub fn parse_owners(owners: &str) -> Result<Vec<UserId>, String> {
+    owners.split(',')
+        .map(|o| o.trim())
+        .filter(|o| !o.is_empty())
+        .map(|o| {
+                 o.parse::<u64>()
+                     .map(UserId)
+                     .map_err(|_| format!("`{}` isn't a Discord user ID", o))
+             })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(permissions: Permissions, role_names: &[&str]) -> Caller {
+        Caller {
+            is_owner: false,
+            server: Some(ServerCaller {
+                             is_server_owner: false,
+                             permissions: permissions,
+                             role_names: role_names.iter().map(|r| r.to_string()).collect(),
+                         }),
+        }
+    }
+
+    #[test]
+    fn nothing_required() {
+        let caller = Caller {
+            is_owner: false,
+            server: None,
+        };
+
+        assert!(caller.check(&[]).is_ok());
+    }
+
+    #[test]
+    fn owner_passes_everything() {
+        let caller = Caller {
+            is_owner: true,
+            server: None,
+        };
+
+        assert!(caller.check(&[Permission::Owner, Permission::ServerAdmin, Permission::Role("Mod")])
+                    .is_ok());
+    }
+
+    #[test]
+    fn owner_required() {
+        let caller = member(Permissions::all(), &[]);
+
+        assert!(caller.check(&[Permission::Owner]).is_err());
+    }
+
+    #[test]
+    fn server_admin() {
+        assert!(member(permissions::ADMINISTRATOR, &[]).check(&[Permission::ServerAdmin]).is_ok());
+        assert!(member(permissions::KICK_MEMBERS, &[]).check(&[Permission::ServerAdmin]).is_err());
+
+        let mut server_owner = member(Permissions::empty(), &[]);
+        server_owner.server.as_mut().unwrap().is_server_owner = true;
+        assert!(server_owner.check(&[Permission::ServerAdmin]).is_ok());
+    }
+
+    #[test]
+    fn server_only_outside_server() {
+        let caller = Caller {
+            is_owner: false,
+            server: None,
+        };
+
+        assert_eq!(caller.check(&[Permission::Role("Mod")]),
+                   Err("That only works in a server.".to_string()));
+    }
+
+    #[test]
+    fn discord_permissions() {
+        let caller = member(permissions::KICK_MEMBERS | permissions::BAN_MEMBERS, &[]);
+
+        assert!(caller.check(&[Permission::Discord(permissions::KICK_MEMBERS)]).is_ok());
+        assert!(caller.check(&[Permission::Discord(permissions::KICK_MEMBERS |
+                                                   permissions::MANAGE_ROLES)])
+                    .is_err());
+    }
+
+    #[test]
+    fn named_roles() {
+        let caller = member(Permissions::empty(), &["Moderator", "FC Officer"]);
+
+        assert!(caller.check(&[Permission::Role("moderator")]).is_ok());
+        assert_eq!(caller.check(&[Permission::Role("Admin")]),
+                   Err("You need the `Admin` role.".to_string()));
+    }
+
+    #[test]
+    fn parse_owner_list() {
+        assert_eq!(parse_owners("1, 22,333"),
+                   Ok(vec![UserId(1), UserId(22), UserId(333)]));
+        assert_eq!(parse_owners(""), Ok(vec![]));
+        assert!(parse_owners("1,me").is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+
+/// A boxed `FnOnce` that can be called.
+pub trait Job: Send {
+    fn run(self: Box<Self>);
+}
+
+impl<F: FnOnce() + Send> Job for F {
+    fn run(self: Box<F>) {
+        (*self)()
+    }
+}
+
+/// A job was refused because every worker is busy and the queue is full.
+#[derive(Debug, PartialEq)]
+pub struct Busy;
+
+/// A fixed number of threads running jobs from a bounded queue.
+pub struct WorkerPool {
+    sender: Mutex<SyncSender<Box<Job>>>,
+    handle: PoolHandle,
+}
+
+/// Lets a job's supervisor give up on the worker running it.
+#[derive(Clone)]
+pub struct PoolHandle {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    receiver: Mutex<Receiver<Box<Job>>>,
+    /// Workers that should exit after their current job, because
+    /// replacements were started for them.
+    retiring: AtomicUsize,
+}
+
+thread_local!(static CURRENT: RefCell<Option<PoolHandle>> = RefCell::new(None));
+
+impl WorkerPool {
+    /// Start `workers` threads, with room for `queue` jobs waiting on them.
+    pub fn new(workers: usize, queue: usize) -> WorkerPool {
+        let (sender, receiver) = mpsc::sync_channel(queue);
+        let handle = PoolHandle {
+            shared: Arc::new(Shared {
+                                 receiver: Mutex::new(receiver),
+                                 retiring: AtomicUsize::new(0),
+                             }),
+        };
+        for _ in 0..workers {
+            handle.spawn_worker();
+        }
+
+        WorkerPool {
+            sender: Mutex::new(sender),
+            handle: handle,
+        }
+    }
+
+    /// Queue `job` to run on the next free worker, unless the queue is full.
+    pub fn try_execute<F>(&self, job: F) -> Result<(), Busy>
+        where F: FnOnce() + Send + 'static
+    {
+        match self.sender.lock().unwrap().try_send(Box::new(job)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) |
+            Err(TrySendError::Disconnected(_)) => Err(Busy),
+        }
+    }
+
+    pub fn handle(&self) -> PoolHandle {
+        self.handle.clone()
+    }
+}
+
+impl PoolHandle {
+    /// The pool the calling thread is a worker of, if any.
+    pub fn current() -> Option<PoolHandle> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// Start a worker to stand in for one stuck on a job, so the pool keeps
+    /// the same number of threads free. The next worker to finish a job
+    /// exits in exchange.
+    pub fn replace_worker(&self) {
+        self.shared.retiring.fetch_add(1, Ordering::SeqCst);
+        self.spawn_worker();
+    }
+
+    fn spawn_worker(&self) {
+        let handle = self.clone();
+        thread::spawn(move || {
+                          CURRENT.with(|current| *current.borrow_mut() = Some(handle.clone()));
+                          handle.work();
+                      });
+    }
+
+    fn work(&self) {
+        loop {
+            let job = match self.shared.receiver.lock().unwrap().recv() {
+                Ok(job) => job,
+                // The pool was dropped.
+                Err(_) => return,
+            };
+            if panic::catch_unwind(AssertUnwindSafe(|| job.run())).is_err() {
+                error!("A job panicked in the worker pool");
+            }
+            if self.retire() {
+                return;
+            }
+        }
+    }
+
+    /// Whether this worker should exit to make up for an earlier replacement.
+    fn retire(&self) -> bool {
+        let retiring = &self.shared.retiring;
+        let mut current = retiring.load(Ordering::SeqCst);
+        while current > 0 {
+            match retiring.compare_exchange(current, current - 1, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn runs_jobs() {
+        let pool = WorkerPool::new(2, 10);
+        let (tx, rx) = channel();
+
+        for i in 0..5 {
+            let tx = tx.clone();
+            pool.try_execute(move || tx.send(i).unwrap()).unwrap();
+        }
+
+        let mut results: Vec<i32> = (0..5).map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap()).collect();
+        results.sort();
+        assert_eq!(results, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn refuses_jobs_when_full() {
+        let pool = WorkerPool::new(1, 1);
+        let (release_tx, release_rx) = channel::<()>();
+        let (started_tx, started_rx) = channel();
+
+        pool.try_execute(move || {
+                             started_tx.send(()).unwrap();
+                             let _ = release_rx.recv();
+                         })
+            .unwrap();
+        started_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        pool.try_execute(|| {}).unwrap();
+
+        assert_eq!(pool.try_execute(|| {}), Err(Busy));
+        release_tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn replaced_worker_frees_the_pool() {
+        let pool = WorkerPool::new(1, 1);
+        let (release_tx, release_rx) = channel::<()>();
+        let (handle_tx, handle_rx) = channel();
+
+        pool.try_execute(move || {
+                             handle_tx.send(PoolHandle::current()).unwrap();
+                             let _ = release_rx.recv();
+                         })
+            .unwrap();
+        let handle = handle_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        handle.expect("jobs know their pool").replace_worker();
+
+        let (done_tx, done_rx) = channel();
+        pool.try_execute(move || done_tx.send(()).unwrap()).unwrap();
+        assert!(done_rx.recv_timeout(Duration::from_secs(5)).is_ok());
+        release_tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn no_current_pool_outside_workers() {
+        assert!(PoolHandle::current().is_none());
+    }
+}
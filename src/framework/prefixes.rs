@@ -0,0 +1,125 @@
+use discord::model::ServerId;
+use std::sync::Arc;
+use storage::{Repository, Storage, StorageError};
+
+/// The longest prefix a server may choose.
+pub const MAX_PREFIX_LENGTH: usize = 10;
+
+/// Command prefixes chosen by each server, falling back to a default.
+pub struct Prefixes {
+    default: String,
+    /// Keyed by server ID.
+    by_server: Repository<String>,
+}
+
+impl Prefixes {
+    /// Prefixes that only last as long as the process.
+    pub fn new(default: &str) -> Prefixes {
+        Prefixes::with_storage(default, Arc::new(Storage::in_memory()))
+    }
+
+    pub fn with_storage(default: &str, storage: Arc<Storage>) -> Prefixes {
+        Prefixes {
+            default: default.to_string(),
+            by_server: Repository::new(storage, "prefixes"),
+        }
+    }
+
+    pub fn default_prefix(&self) -> &str {
+        &self.default
+    }
+
+    pub fn get(&self, server: Option<ServerId>) -> String {
+        let server = match server {
+            Some(s) => s,
+            None => return self.default.clone(),
+        };
+
+        match self.by_server.get(&server.0.to_string()) {
+            Ok(Some(p)) => p,
+            Ok(None) => self.default.clone(),
+            Err(e) => {
+                error!("Unable to read the prefix for server {}: {}", server, e);
+                self.default.clone()
+            }
+        }
+    }
+
+    pub fn set(&self, server: ServerId, prefix: &str) -> Result<(), StorageError> {
+        self.by_server.put(&server.0.to_string(), &prefix.to_string())
+    }
+
+    /// Go back to using the default prefix for `server`.
+    pub fn reset(&self, server: ServerId) -> Result<(), StorageError> {
+        self.by_server.remove(&server.0.to_string())?;
+        Ok(())
+    }
+}
+
+/// Why `prefix` can't be used as a command prefix, in a form fit to show the user.
+pub fn validate(prefix: &str) -> Result<(), String> {
+    if prefix.is_empty() {
+        return Err("Prefixes can't be empty.".to_string());
+    }
+    if prefix.chars().any(char::is_whitespace) {
+        return Err("Prefixes can't contain spaces.".to_string());
+    }
+    if prefix.chars().count() > MAX_PREFIX_LENGTH {
+        return Err(format!("Prefixes can be at most {} characters long.", MAX_PREFIX_LENGTH));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::MemoryBackend;
+
+    #[test]
+    fn get_default_without_server() {
+        let prefixes = Prefixes::new("!");
+
+        assert_eq!(prefixes.get(None), "!");
+        assert_eq!(prefixes.get(Some(ServerId(1))), "!");
+    }
+
+    #[test]
+    fn set_and_reset() {
+        let prefixes = Prefixes::new("!");
+        prefixes.set(ServerId(1), "?").unwrap();
+
+        assert_eq!(prefixes.get(Some(ServerId(1))), "?");
+        assert_eq!(prefixes.get(Some(ServerId(2))), "!");
+
+        prefixes.reset(ServerId(1)).unwrap();
+        assert_eq!(prefixes.get(Some(ServerId(1))), "!");
+    }
+
+    #[test]
+    fn saved_prefixes_survive_reopening() {
+        let backend = Arc::new(MemoryBackend::new());
+        {
+            let storage = Storage::open(Box::new(backend.clone()), &[]).unwrap();
+            let prefixes = Prefixes::with_storage("!", Arc::new(storage));
+            prefixes.set(ServerId(1), "?").unwrap();
+            prefixes.set(ServerId(2), "tb!").unwrap();
+            prefixes.reset(ServerId(2)).unwrap();
+        }
+
+        let storage = Storage::open(Box::new(backend), &[]).unwrap();
+        let prefixes = Prefixes::with_storage("!", Arc::new(storage));
+
+        assert_eq!(prefixes.get(Some(ServerId(1))), "?");
+        assert_eq!(prefixes.get(Some(ServerId(2))), "!");
+    }
+
+    #[test]
+    fn validate_prefixes() {
+        assert_eq!(validate("!"), Ok(()));
+        assert_eq!(validate("tb!"), Ok(()));
+        assert!(validate("").is_err());
+        assert!(validate("t b").is_err());
+        assert!(validate("abcdefghijk").is_err());
+    }
+}
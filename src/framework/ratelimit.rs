@@ -0,0 +1,179 @@
+use discord::model::ChannelId;
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Allows at most `limit` uses in any `window` long stretch of time.
+pub struct SlidingWindow {
+    limit: usize,
+    window: Duration,
+    uses: VecDeque<Instant>,
+}
+
+impl SlidingWindow {
+    pub fn new(limit: usize, window: Duration) -> SlidingWindow {
+        SlidingWindow {
+            limit: limit,
+            window: window,
+            uses: VecDeque::new(),
+        }
+    }
+
+    /// How long until another use is allowed, or `None` if one is allowed now.
+    pub fn wait_time(&mut self, now: Instant) -> Option<Duration> {
+        while let Some(&oldest) = self.uses.front() {
+            if now.duration_since(oldest) >= self.window {
+                self.uses.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if self.uses.len() < self.limit {
+            None
+        } else {
+            // With a limit of zero there are no uses to wait out, and no use
+            // is ever allowed.
+            Some(self.uses.front().map_or(self.window, |&oldest| {
+                self.window - now.duration_since(oldest)
+            }))
+        }
+    }
+
+    pub fn record(&mut self, now: Instant) {
+        self.uses.push_back(now);
+    }
+
+    /// Take back the use recorded at `at`, as if it never happened. Uses
+    /// recorded at the same moment are alike, so it doesn't matter which.
+    pub fn forget(&mut self, at: Instant) {
+        if let Some(i) = self.uses.iter().rposition(|&used| used == at) {
+            self.uses.remove(i);
+        }
+    }
+
+    /// Record a use if one is allowed, otherwise say how long until it will be.
+    pub fn try_use(&mut self, now: Instant) -> Result<(), Duration> {
+        match self.wait_time(now) {
+            Some(wait) => Err(wait),
+            None => {
+                self.record(now);
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether every recorded use has aged out of the window.
+    pub fn is_idle(&self, now: Instant) -> bool {
+        self.uses.back().map_or(true, |&newest| now.duration_since(newest) >= self.window)
+    }
+}
+
+/// Once this many channels are being tracked, idle ones are forgotten.
+const MAX_IDLE_CHANNELS: usize = 1000;
+
+/// Keeps the bot's own messages within Discord's documented limits: 5
+/// messages per channel every 5 seconds, and 50 requests a second overall.
+pub struct RateLimiter {
+    windows: Mutex<Windows>,
+}
+
+struct Windows {
+    global: SlidingWindow,
+    channels: HashMap<ChannelId, SlidingWindow>,
+}
+
+impl RateLimiter {
+    pub fn new() -> RateLimiter {
+        RateLimiter {
+            windows: Mutex::new(Windows {
+                                    global: SlidingWindow::new(50, Duration::from_secs(1)),
+                                    channels: HashMap::new(),
+                                }),
+        }
+    }
+
+    /// Block until a message can be sent to `channel`, and count it.
+    pub fn wait(&self, channel: ChannelId) {
+        loop {
+            let wait = {
+                let mut windows = self.windows.lock().unwrap();
+                let now = Instant::now();
+                let Windows { ref mut global, ref mut channels } = *windows;
+                if channels.len() > MAX_IDLE_CHANNELS {
+                    channels.retain(|_, w| !w.is_idle(now));
+                }
+                let channel_window = channels.entry(channel)
+                    .or_insert_with(|| SlidingWindow::new(5, Duration::from_secs(5)));
+
+                match cmp::max(global.wait_time(now), channel_window.wait_time(now)) {
+                    Some(wait) => wait,
+                    None => {
+                        global.record(now);
+                        channel_window.record(now);
+                        return;
+                    }
+                }
+            };
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_up_to_limit() {
+        let start = Instant::now();
+        let mut window = SlidingWindow::new(2, Duration::from_secs(10));
+
+        assert_eq!(window.try_use(start), Ok(()));
+        assert_eq!(window.try_use(start + Duration::from_secs(1)), Ok(()));
+        assert_eq!(window.try_use(start + Duration::from_secs(2)),
+                   Err(Duration::from_secs(8)));
+    }
+
+    #[test]
+    fn uses_age_out() {
+        let start = Instant::now();
+        let mut window = SlidingWindow::new(1, Duration::from_secs(10));
+
+        assert_eq!(window.try_use(start), Ok(()));
+        assert!(!window.is_idle(start + Duration::from_secs(9)));
+        assert!(window.is_idle(start + Duration::from_secs(10)));
+        assert_eq!(window.try_use(start + Duration::from_secs(10)), Ok(()));
+    }
+
+    #[test]
+    fn rejected_uses_are_not_recorded() {
+        let start = Instant::now();
+        let mut window = SlidingWindow::new(1, Duration::from_secs(10));
+
+        assert_eq!(window.try_use(start), Ok(()));
+        assert!(window.try_use(start + Duration::from_secs(5)).is_err());
+        assert_eq!(window.try_use(start + Duration::from_secs(10)), Ok(()));
+    }
+
+    #[test]
+    fn zero_limit_allows_nothing() {
+        let mut window = SlidingWindow::new(0, Duration::from_secs(10));
+
+        assert_eq!(window.try_use(Instant::now()), Err(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn rate_limiter_does_not_block_under_limit() {
+        let limiter = RateLimiter::new();
+        let start = Instant::now();
+        for _ in 0..5 {
+            limiter.wait(ChannelId(1));
+        }
+        limiter.wait(ChannelId(2));
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,467 @@
+use framework::command::{Arity, Command};
+use framework::group::CommandGroup;
+use std::cmp;
+use std::slice;
+use util::edit_distance::levenshtein;
+
+pub enum Entry {
+    Command(Box<Command>),
+    Group(CommandGroup),
+}
+
+impl Entry {
+    pub fn name(&self) -> &'static str {
+        match self {
+            &Entry::Command(ref cmd) => cmd.name(),
+            &Entry::Group(ref group) => group.name(),
+        }
+    }
+
+    pub fn description(&self) -> Option<&'static str> {
+        match self {
+            &Entry::Command(ref cmd) => cmd.description(),
+            &Entry::Group(ref group) => group.description(),
+        }
+    }
+
+    fn names(&self) -> Vec<&'static str> {
+        let mut names = vec![self.name()];
+        if let &Entry::Command(ref cmd) = self {
+            names.extend(cmd.aliases().iter().cloned());
+        }
+        names
+    }
+}
+
+/// What a message resolved to. `path` is the full, canonical name used to
+/// reach it (e.g. `ffxiv events`), and `args` the unparsed remainder.
+pub enum Lookup<'a, 'm> {
+    Command {
+        path: String,
+        command: &'a Command,
+        args: &'m str,
+    },
+    /// A group was named, but none of its subcommands were.
+    Group {
+        path: String,
+        group: &'a CommandGroup,
+        args: &'m str,
+    },
+}
+
+enum Target<'a> {
+    Command(&'a Command),
+    Group(&'a CommandGroup),
+}
+
+pub struct CommandRegistry {
+    entries: Vec<Entry>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> CommandRegistry {
+        CommandRegistry { entries: Vec::new() }
+    }
+
+    /// Panics if `command`'s arguments can't be matched to what's typed:
+    /// only the last positional argument can be variadic or take the rest.
+    pub fn register<C: Command + 'static>(&mut self, command: C) {
+        check_arguments(&command);
+        self.entries.push(Entry::Command(Box::new(command)));
+    }
+
+    pub fn register_group(&mut self, group: CommandGroup) {
+        self.entries.push(Entry::Group(group));
+    }
+
+    pub fn entries(&self) -> slice::Iter<Entry> {
+        self.entries.iter()
+    }
+
+    /// Find what `message` (with the prefix already removed) invokes.
+    ///
+    /// Names only match whole words, and when several commands match the one
+    /// with the longest path wins.
+    pub fn find<'a, 'm>(&'a self, message: &'m str) -> Option<Lookup<'a, 'm>> {
+        let words = word_spans(message);
+        let lowercase_words: Vec<String> = words.iter()
+            .map(|&(start, end)| message[start..end].to_lowercase())
+            .collect();
+
+        self.lookup(&lowercase_words).map(|(path, target, matched_words)| {
+            let args = message[words[matched_words - 1].1..].trim();
+            let path = path.join(" ");
+            match target {
+                Target::Command(command) => {
+                    Lookup::Command {
+                        path: path,
+                        command: command,
+                        args: args,
+                    }
+                }
+                Target::Group(group) => {
+                    Lookup::Group {
+                        path: path,
+                        group: group,
+                        args: args,
+                    }
+                }
+            }
+        })
+    }
+
+    fn lookup<'a>(&'a self, words: &[String]) -> Option<(Vec<&'static str>, Target<'a>, usize)> {
+        let mut best: Option<(Vec<&'static str>, Target<'a>, usize)> = None;
+        for entry in &self.entries {
+            for name in entry.names() {
+                let name_words: Vec<&str> = name.split_whitespace().collect();
+                if name_words.len() > words.len() ||
+                   name_words.iter().zip(words.iter()).any(|(n, w)| n != w) {
+                    continue;
+                }
+
+                let candidate = match entry {
+                    &Entry::Command(ref cmd) => {
+                        (vec![cmd.name()], Target::Command(cmd.as_ref()), name_words.len())
+                    }
+                    &Entry::Group(ref group) => {
+                        match group.commands().lookup(&words[name_words.len()..]) {
+                            Some((mut path, target, matched_words)) => {
+                                path.insert(0, group.name());
+                                (path, target, name_words.len() + matched_words)
+                            }
+                            None => (vec![group.name()], Target::Group(group), name_words.len()),
+                        }
+                    }
+                };
+                let is_longer = match best {
+                    Some((_, _, matched_words)) => candidate.2 > matched_words,
+                    None => true,
+                };
+                if is_longer {
+                    best = Some(candidate);
+                }
+            }
+        }
+
+        best
+    }
+
+    /// The full command path closest to what `message` (with the prefix
+    /// already removed) starts with, if any is close enough to be a likely
+    /// typo.
+    pub fn suggest(&self, message: &str) -> Option<String> {
+        let words: Vec<String> = message.split_whitespace().map(|w| w.to_lowercase()).collect();
+        if words.is_empty() {
+            return None;
+        }
+
+        // Ties go to the path with more words, since it matched more of the message.
+        let mut best: Option<(String, usize, usize)> = None;
+        for path in self.paths() {
+            let path_word_count = path.split_whitespace().count();
+            let attempted = words[..cmp::min(path_word_count, words.len())].join(" ");
+            let distance = levenshtein(&attempted, &path);
+            if distance > cmp::max(2, path.chars().count() / 3) {
+                continue;
+            }
+            let is_closer = match best {
+                Some((_, best_distance, best_word_count)) => {
+                    distance < best_distance ||
+                    (distance == best_distance && path_word_count > best_word_count)
+                }
+                None => true,
+            };
+            if is_closer {
+                best = Some((path, distance, path_word_count));
+            }
+        }
+
+        best.map(|(path, _, _)| path)
+    }
+
+    /// Every name and alias reachable from this registry, as full paths.
+    fn paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        for entry in &self.entries {
+            for name in entry.names() {
+                paths.push(name.to_string());
+            }
+            if let &Entry::Group(ref group) = entry {
+                for path in group.commands().paths() {
+                    paths.push(format!("{} {}", group.name(), path));
+                }
+            }
+        }
+
+        paths
+    }
+}
+
+/// Byte offsets of the start and end of each whitespace separated word.
+fn word_spans(message: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (idx, c) in message.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push((s, idx));
+                start = None;
+            }
+            (false, None) => start = Some(idx),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, message.len()));
+    }
+
+    spans
+}
+
+fn check_arguments(command: &Command) {
+    let positional = command.arguments().iter().filter(|a| !a.is_named());
+    if let Some(argument) = positional.rev()
+        .skip(1)
+        .find(|a| a.arity == Arity::Variadic || a.arity == Arity::Rest) {
+        panic!("`{}` takes `{}` before other arguments, but only the last positional argument \
+                can be variadic or take the rest",
+               command.name(),
+               argument.name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use framework::args::Args;
+    use framework::command::{Argument, CommandResult, Context};
+
+    struct TestCommand {
+        name: &'static str,
+        aliases: &'static [&'static str],
+    }
+
+    impl Command for TestCommand {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn aliases(&self) -> &'static [&'static str] {
+            self.aliases
+        }
+
+        fn exec(&self, _ctx: &Context, _args: Args) -> CommandResult {
+            Ok(())
+        }
+    }
+
+    fn command(name: &'static str) -> TestCommand {
+        TestCommand {
+            name: name,
+            aliases: &[],
+        }
+    }
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::new();
+        registry.register(command("ping"));
+        registry.register(TestCommand {
+                              name: "8-ball",
+                              aliases: &["8ball", "eightball"],
+                          });
+
+        let mut ffxiv = CommandGroup::new("ffxiv", "FF XIV things");
+        ffxiv.register(command("events"));
+        ffxiv.register(command("resets"));
+        registry.register_group(ffxiv);
+
+        let mut admin = CommandGroup::new("admin", "Admin things");
+        admin.register(command("status"));
+        admin.set_default("status");
+        registry.register_group(admin);
+
+        registry
+    }
+
+    fn found_command<'a, 'm>(lookup: Option<Lookup<'a, 'm>>) -> (String, &'a Command, &'m str) {
+        match lookup {
+            Some(Lookup::Command { path, command, args }) => (path, command, args),
+            Some(Lookup::Group { path, .. }) => panic!("Expected a command, found group {}", path),
+            None => panic!("Expected a command, found nothing"),
+        }
+    }
+
+    fn found_group<'a, 'm>(lookup: Option<Lookup<'a, 'm>>) -> (String, &'a CommandGroup, &'m str) {
+        match lookup {
+            Some(Lookup::Group { path, group, args }) => (path, group, args),
+            Some(Lookup::Command { path, .. }) => {
+                panic!("Expected a group, found command {}", path)
+            }
+            None => panic!("Expected a group, found nothing"),
+        }
+    }
+
+    #[test]
+    fn find_by_name() {
+        let registry = registry();
+        let (path, cmd, args) = found_command(registry.find("ping"));
+
+        assert_eq!(path, "ping");
+        assert_eq!(cmd.name(), "ping");
+        assert_eq!(args, "");
+    }
+
+    #[test]
+    fn find_by_alias() {
+        let registry = registry();
+        let (path, cmd, args) = found_command(registry.find("eightball will it work?"));
+
+        assert_eq!(path, "8-ball");
+        assert_eq!(cmd.name(), "8-ball");
+        assert_eq!(args, "will it work?");
+    }
+
+    #[test]
+    fn find_ignores_case_but_keeps_argument_case() {
+        let registry = registry();
+        let (_, cmd, args) = found_command(registry.find("PING Hello"));
+
+        assert_eq!(cmd.name(), "ping");
+        assert_eq!(args, "Hello");
+    }
+
+    #[test]
+    fn find_unknown_command() {
+        let registry = registry();
+
+        assert!(registry.find("roll 1d20").is_none());
+    }
+
+    #[test]
+    fn find_requires_whole_words() {
+        let registry = registry();
+
+        assert!(registry.find("pingxyz").is_none());
+
+        let (path, _, args) = found_group(registry.find("ffxiv resetsfoo"));
+        assert_eq!(path, "ffxiv");
+        assert_eq!(args, "resetsfoo");
+    }
+
+    #[test]
+    fn find_subcommand() {
+        let registry = registry();
+        let (path, cmd, args) = found_command(registry.find("ffxiv  Events upcoming"));
+
+        assert_eq!(path, "ffxiv events");
+        assert_eq!(cmd.name(), "events");
+        assert_eq!(args, "upcoming");
+    }
+
+    #[test]
+    fn find_group_without_subcommand() {
+        let registry = registry();
+        let (path, group, args) = found_group(registry.find("ffxiv"));
+
+        assert_eq!(path, "ffxiv");
+        assert_eq!(group.name(), "ffxiv");
+        assert!(group.default_command().is_none());
+        assert_eq!(args, "");
+    }
+
+    #[test]
+    fn group_default_command() {
+        let registry = registry();
+        let (_, group, _) = found_group(registry.find("admin"));
+
+        assert_eq!(group.default_command().map(|c| c.name()), Some("status"));
+    }
+
+    #[test]
+    fn group_default_by_alias() {
+        let mut group = CommandGroup::new("fun", "Fun things");
+        group.register(TestCommand {
+                           name: "8-ball",
+                           aliases: &["8ball"],
+                       });
+        group.set_default("8ball");
+
+        assert_eq!(group.default_command().map(|c| c.name()), Some("8-ball"));
+    }
+
+    #[test]
+    fn find_with_only_whitespace() {
+        let registry = registry();
+
+        assert!(registry.find("").is_none());
+        assert!(registry.find("   ").is_none());
+    }
+
+    #[test]
+    fn suggest_close_name() {
+        let registry = registry();
+
+        assert_eq!(registry.suggest("pingxy"), Some("ping".to_string()));
+        assert_eq!(registry.suggest("8bal what?"), Some("8ball".to_string()));
+        assert_eq!(registry.suggest("ffxv events"),
+                   Some("ffxiv events".to_string()));
+    }
+
+    #[test]
+    fn suggest_within_group() {
+        let registry = registry();
+        let (_, group, args) = found_group(registry.find("ffxiv evnts"));
+
+        assert_eq!(group.commands().suggest(args), Some("events".to_string()));
+    }
+
+    #[test]
+    fn suggest_nothing_when_too_different() {
+        let registry = registry();
+
+        assert_eq!(registry.suggest("weather tomorrow"), None);
+        assert_eq!(registry.suggest(""), None);
+    }
+
+    #[test]
+    fn entries_in_registration_order() {
+        let registry = registry();
+        let names: Vec<&str> = registry.entries().map(|e| e.name()).collect();
+
+        assert_eq!(names, vec!["ping", "8-ball", "ffxiv", "admin"]);
+    }
+
+    struct RestFirst;
+
+    impl Command for RestFirst {
+        fn name(&self) -> &'static str {
+            "say"
+        }
+
+        fn arguments(&self) -> &'static [Argument] {
+        &[Argument {
+              name: "message",
+              description: "",
+              arity: Arity::Rest,
+          },
+          Argument {
+              name: "channel",
+              description: "",
+              arity: Arity::Required,
+          }]
+        }
+
+        fn exec(&self, _ctx: &Context, _args: Args) -> CommandResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "only the last positional argument")]
+    fn only_the_last_argument_takes_the_rest() {
+        let mut group = CommandGroup::new("admin", "Admin things");
+
+        group.register(RestFirst);
+    }
+}
@@ -0,0 +1,235 @@
+use discord::model::MessageId;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// What a reply was sent as. Only replies of the same kind are edited, since
+/// editing an embed into a text message would leave the embed behind.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplyKind {
+    Text,
+    Embed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reply {
+    pub id: MessageId,
+    pub kind: ReplyKind,
+}
+
+/// The replies to one run of a command. Replies from before the command
+/// message was edited are edited in turn instead of sending new ones.
+pub struct Replies {
+    previous: Mutex<VecDeque<Reply>>,
+    /// Previous replies passed over because they were the wrong kind.
+    skipped: Mutex<Vec<Reply>>,
+    sent: Mutex<Vec<Reply>>,
+}
+
+impl Replies {
+    pub fn new(previous: Vec<Reply>) -> Replies {
+        Replies {
+            previous: Mutex::new(previous.into_iter().collect()),
+            skipped: Mutex::new(Vec::new()),
+            sent: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The earlier reply to edit instead of sending a new `kind` of reply,
+    /// if there is one.
+    pub fn reuse(&self, kind: ReplyKind) -> Option<MessageId> {
+        // Whatever the next one is, it can't be used for anything else now.
+        match self.previous.lock().unwrap().pop_front() {
+            Some(reply) if reply.kind == kind => Some(reply.id),
+            Some(reply) => {
+                self.skipped.lock().unwrap().push(reply);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// The earlier replies that weren't reused, which are left over from
+    /// before the edit and should go.
+    pub fn unused(&self) -> Vec<Reply> {
+        let mut unused: Vec<Reply> = self.skipped.lock().unwrap().drain(..).collect();
+        unused.extend(self.previous.lock().unwrap().drain(..));
+        unused
+    }
+
+    pub fn record(&self, reply: Reply) {
+        self.sent.lock().unwrap().push(reply);
+    }
+
+    /// Everything sent or edited this run, in order.
+    pub fn sent(&self) -> Vec<Reply> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+/// Which replies the bot sent to recent commands, and what each command
+/// said, so that when a command message is edited its replies can be edited
+/// to match. Only the newest `capacity` commands within `max_age` are
+/// remembered.
+pub struct RecentReplies {
+    capacity: usize,
+    max_age: Duration,
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+struct Entry {
+    command: MessageId,
+    content: String,
+    at: Instant,
+    replies: Vec<Reply>,
+}
+
+impl RecentReplies {
+    pub fn new(capacity: usize, max_age: Duration) -> RecentReplies {
+        RecentReplies {
+            capacity: capacity,
+            max_age: max_age,
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Remember `replies` as the latest answer to `command`, which said
+    /// `content`.
+    pub fn record(&self, command: MessageId, content: &str, replies: Vec<Reply>, now: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|e| e.command != command);
+        entries.push_back(Entry {
+                              command: command,
+                              content: content.to_string(),
+                              at: now,
+                              replies: replies,
+                          });
+        while entries.len() > self.capacity {
+            entries.pop_front();
+        }
+        while entries.front().map_or(false, |e| now.duration_since(e.at) > self.max_age) {
+            entries.pop_front();
+        }
+    }
+
+    /// The replies to `command` if it's a recent command that said something
+    /// other than `content` last time, or `None`. Discord sends message
+    /// updates for more than edits, like pinning, and those shouldn't run the
+    /// command again.
+    pub fn changed(&self, command: MessageId, content: &str, now: Instant) -> Option<Vec<Reply>> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter()
+            .find(|e| e.command == command && now.duration_since(e.at) <= self.max_age)
+            .and_then(|e| if e.content != content {
+                          Some(e.replies.clone())
+                      } else {
+                          None
+                      })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EDITED: &'static str = "!roll 1d20";
+
+    fn text(id: u64) -> Reply {
+        Reply {
+            id: MessageId(id),
+            kind: ReplyKind::Text,
+        }
+    }
+
+    #[test]
+    fn reuse_matching_replies_in_order() {
+        let replies = Replies::new(vec![text(1),
+                                        Reply {
+                                            id: MessageId(2),
+                                            kind: ReplyKind::Embed,
+                                        },
+                                        text(3)]);
+
+        assert_eq!(replies.reuse(ReplyKind::Text), Some(MessageId(1)));
+        assert_eq!(replies.reuse(ReplyKind::Text), None);
+        assert_eq!(replies.reuse(ReplyKind::Text), Some(MessageId(3)));
+        assert_eq!(replies.reuse(ReplyKind::Text), None);
+    }
+
+    #[test]
+    fn unused_replies_include_skipped_ones() {
+        let replies = Replies::new(vec![Reply {
+                                            id: MessageId(1),
+                                            kind: ReplyKind::Embed,
+                                        },
+                                        text(2),
+                                        text(3)]);
+
+        assert_eq!(replies.reuse(ReplyKind::Text), None);
+        assert_eq!(replies.reuse(ReplyKind::Text), Some(MessageId(2)));
+        assert_eq!(replies.unused().iter().map(|r| r.id).collect::<Vec<_>>(),
+                   vec![MessageId(1), MessageId(3)]);
+        assert_eq!(replies.unused(), vec![]);
+    }
+
+    #[test]
+    fn recent_replies_are_found() {
+        let recent = RecentReplies::new(10, Duration::from_secs(60));
+        let now = Instant::now();
+
+        recent.record(MessageId(100), "!roll", vec![text(1)], now);
+        recent.record(MessageId(101), "!ping", vec![], now);
+
+        assert_eq!(recent.changed(MessageId(100), EDITED, now), Some(vec![text(1)]));
+        assert_eq!(recent.changed(MessageId(101), EDITED, now), Some(vec![]));
+        assert_eq!(recent.changed(MessageId(102), EDITED, now), None);
+    }
+
+    #[test]
+    fn unchanged_commands_are_not_found() {
+        let recent = RecentReplies::new(10, Duration::from_secs(60));
+        let now = Instant::now();
+
+        recent.record(MessageId(100), "!roll", vec![text(1)], now);
+
+        assert_eq!(recent.changed(MessageId(100), "!roll", now), None);
+        assert_eq!(recent.changed(MessageId(100), "!roll 2d6", now), Some(vec![text(1)]));
+    }
+
+    #[test]
+    fn rerecording_replaces() {
+        let recent = RecentReplies::new(10, Duration::from_secs(60));
+        let now = Instant::now();
+
+        recent.record(MessageId(100), "!roll", vec![text(1)], now);
+        recent.record(MessageId(100), "!roll", vec![text(1), text(2)], now);
+
+        assert_eq!(recent.changed(MessageId(100), EDITED, now), Some(vec![text(1), text(2)]));
+    }
+
+    #[test]
+    fn oldest_are_forgotten_past_capacity() {
+        let recent = RecentReplies::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+
+        for id in 0..3 {
+            recent.record(MessageId(id), "!ping", vec![text(id + 10)], now);
+        }
+
+        assert_eq!(recent.changed(MessageId(0), EDITED, now), None);
+        assert!(recent.changed(MessageId(2), EDITED, now).is_some());
+    }
+
+    #[test]
+    fn old_replies_expire() {
+        let recent = RecentReplies::new(10, Duration::from_secs(60));
+        let now = Instant::now();
+
+        recent.record(MessageId(100), "!roll", vec![text(1)], now);
+
+        assert!(recent.changed(MessageId(100), EDITED, now + Duration::from_secs(60)).is_some());
+        assert_eq!(recent.changed(MessageId(100), EDITED, now + Duration::from_secs(61)), None);
+        recent.record(MessageId(101), "!ping", vec![], now + Duration::from_secs(61));
+        assert_eq!(recent.entries.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,190 @@
+use discord::model::ChannelId;
+use framework::transport::Responder;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// What happens once the bot has stopped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShutdownKind {
+    Stop,
+    Restart,
+}
+
+#[derive(Clone)]
+pub struct ShutdownRequest {
+    pub kind: ShutdownKind,
+    /// Where to confirm the shutdown, when a command asked for it.
+    pub reply_to: Option<(Arc<Responder>, ChannelId)>,
+}
+
+impl ShutdownRequest {
+    /// A request from outside Discord, like a signal, with no one to confirm to.
+    pub fn new(kind: ShutdownKind) -> ShutdownRequest {
+        ShutdownRequest {
+            kind: kind,
+            reply_to: None,
+        }
+    }
+}
+
+/// Lets the bot stop without cutting commands off: once a shutdown is
+/// requested no new commands start, and whoever is shutting down can wait for
+/// the running ones to finish.
+pub struct Shutdown {
+    state: Mutex<State>,
+    requested: Condvar,
+    idle: Condvar,
+}
+
+struct State {
+    request: Option<ShutdownRequest>,
+    running: usize,
+}
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        Shutdown {
+            state: Mutex::new(State {
+                                  request: None,
+                                  running: 0,
+                              }),
+            requested: Condvar::new(),
+            idle: Condvar::new(),
+        }
+    }
+
+    /// Ask the bot to stop. Only the first request counts; returns whether
+    /// this was it.
+    pub fn request(&self, request: ShutdownRequest) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.request.is_some() {
+            return false;
+        }
+
+        state.request = Some(request);
+        self.requested.notify_all();
+        true
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.state.lock().unwrap().request.is_some()
+    }
+
+    /// Block until a shutdown is requested.
+    pub fn wait(&self) -> ShutdownRequest {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(ref request) = state.request {
+                return request.clone();
+            }
+            state = self.requested.wait(state).unwrap();
+        }
+    }
+
+    /// Mark a command as running until the returned guard is dropped, unless
+    /// the bot is shutting down, in which case it shouldn't run at all.
+    pub fn start(&self) -> Option<Running> {
+        let mut state = self.state.lock().unwrap();
+        if state.request.is_some() {
+            return None;
+        }
+
+        state.running += 1;
+        Some(Running { shutdown: self })
+    }
+
+    /// Wait up to `timeout` for running commands to finish. Returns how many
+    /// are still running.
+    pub fn wait_idle(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().unwrap();
+        while state.running > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self.idle.wait_timeout(state, deadline - now).unwrap().0;
+        }
+
+        state.running
+    }
+}
+
+/// A running command. See `Shutdown::start`.
+pub struct Running<'a> {
+    shutdown: &'a Shutdown,
+}
+
+impl<'a> Drop for Running<'a> {
+    fn drop(&mut self) {
+        let mut state = self.shutdown.state.lock().unwrap();
+        state.running -= 1;
+        if state.running == 0 {
+            self.shutdown.idle.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn first_request_wins() {
+        let shutdown = Shutdown::new();
+
+        assert!(!shutdown.is_requested());
+        assert!(shutdown.request(ShutdownRequest::new(ShutdownKind::Restart)));
+        assert!(!shutdown.request(ShutdownRequest::new(ShutdownKind::Stop)));
+        assert_eq!(shutdown.wait().kind, ShutdownKind::Restart);
+    }
+
+    #[test]
+    fn nothing_starts_after_request() {
+        let shutdown = Shutdown::new();
+        let running = shutdown.start();
+        assert!(running.is_some());
+
+        shutdown.request(ShutdownRequest::new(ShutdownKind::Stop));
+
+        assert!(shutdown.start().is_none());
+        assert_eq!(shutdown.wait_idle(Duration::from_millis(10)), 1);
+        drop(running);
+        assert_eq!(shutdown.wait_idle(Duration::from_millis(10)), 0);
+    }
+
+    #[test]
+    fn wait_idle_waits_for_running_commands() {
+        let shutdown = Arc::new(Shutdown::new());
+        let (started_tx, started_rx) = mpsc::channel();
+        let worker = {
+            let shutdown = shutdown.clone();
+            thread::spawn(move || {
+                let _running = shutdown.start();
+                started_tx.send(()).unwrap();
+                thread::sleep(Duration::from_millis(50));
+            })
+        };
+        started_rx.recv().unwrap();
+
+        shutdown.request(ShutdownRequest::new(ShutdownKind::Stop));
+
+        assert_eq!(shutdown.wait_idle(Duration::from_secs(5)), 0);
+        worker.join().unwrap();
+    }
+
+    #[test]
+    fn wait_returns_once_requested() {
+        let shutdown = Arc::new(Shutdown::new());
+        let waiter = {
+            let shutdown = shutdown.clone();
+            thread::spawn(move || shutdown.wait().kind)
+        };
+
+        shutdown.request(ShutdownRequest::new(ShutdownKind::Stop));
+
+        assert_eq!(waiter.join().unwrap(), ShutdownKind::Stop);
+    }
+}
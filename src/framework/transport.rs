@@ -0,0 +1,177 @@
+use discord::{ChannelRef, Discord, State};
+use discord::model::{ChannelId, Message, MessageId, ReactionEmoji, ServerId, User, UserId};
+use discord::builders::EmbedBuilder;
+use framework::command::{CommandError, CommandResult};
+use framework::embed::Embed;
+use framework::entities::Directory;
+use framework::permissions::ServerCaller;
+use std::sync::Arc;
+
+/// Who sent a message.
+#[derive(Clone, Debug)]
+pub struct Author {
+    pub id: UserId,
+    pub name: String,
+}
+
+/// A message that might be a command, along with what the dispatcher needs
+/// to know about where it came from.
+#[derive(Clone, Debug)]
+pub struct IncomingMessage {
+    pub id: MessageId,
+    pub channel_id: ChannelId,
+    /// The server the message was sent in, or `None` for private messages.
+    pub server_id: Option<ServerId>,
+    pub author: Author,
+    /// The author's standing in the server, or `None` for private messages.
+    /// Left `None` by `incoming_message` and `edited_message`, since it's
+    /// costly to work out for every message: see `add_server_details`.
+    pub member: Option<ServerCaller>,
+    /// What's in the server, for arguments that refer to it by name. Left
+    /// `None` like `member`.
+    pub directory: Option<Directory>,
+    pub content: String,
+}
+
+/// How commands talk back to the chat they were sent from. Shared with the
+/// threads that watch for commands timing out, so must be `Send + Sync`.
+pub trait Responder: Send + Sync {
+    /// The bot's own user ID.
+    fn user_id(&self) -> UserId;
+
+    fn send_message(&self, channel: ChannelId, text: &str) -> Result<MessageId, CommandError>;
+
+    fn send_embed(&self, channel: ChannelId, embed: &Embed) -> Result<MessageId, CommandError>;
+
+    /// Replace the text of a message the bot sent.
+    fn edit_message(&self, channel: ChannelId, message: MessageId, text: &str) -> CommandResult;
+
+    /// Replace the embed of a message the bot sent.
+    fn edit_embed(&self, channel: ChannelId, message: MessageId, embed: &Embed) -> CommandResult;
+
+    /// Delete a message the bot sent.
+    fn delete_message(&self, channel: ChannelId, message: MessageId) -> CommandResult;
+
+    /// React to `message` with a unicode emoji.
+    fn react(&self, channel: ChannelId, message: MessageId, emoji: &str) -> Result<(), CommandError>;
+
+    fn send_private_message(&self, user: UserId, text: &str) -> Result<MessageId, CommandError>;
+}
+
+pub struct DiscordResponder {
+    discord: Arc<Discord>,
+    user_id: UserId,
+}
+
+impl DiscordResponder {
+    pub fn new(discord: Arc<Discord>, user_id: UserId) -> DiscordResponder {
+        DiscordResponder {
+            discord: discord,
+            user_id: user_id,
+        }
+    }
+}
+
+impl Responder for DiscordResponder {
+    fn user_id(&self) -> UserId {
+        self.user_id
+    }
+
+    fn send_message(&self, channel: ChannelId, text: &str) -> Result<MessageId, CommandError> {
+        Ok(self.discord.send_message(channel, text, "", false)?.id)
+    }
+
+    fn send_embed(&self, channel: ChannelId, embed: &Embed) -> Result<MessageId, CommandError> {
+        let sent = self.discord.send_embed(channel, "", |e| build_embed(e, embed))?;
+        Ok(sent.id)
+    }
+
+    fn edit_message(&self, channel: ChannelId, message: MessageId, text: &str) -> CommandResult {
+        self.discord.edit_message(channel, message, text)?;
+        Ok(())
+    }
+
+    fn edit_embed(&self, channel: ChannelId, message: MessageId, embed: &Embed) -> CommandResult {
+        self.discord.edit_embed(channel, message, |e| build_embed(e, embed))?;
+        Ok(())
+    }
+
+    fn delete_message(&self, channel: ChannelId, message: MessageId) -> CommandResult {
+        Ok(self.discord.delete_message(channel, message)?)
+    }
+
+    fn react(&self, channel: ChannelId, message: MessageId, emoji: &str) -> Result<(), CommandError> {
+        Ok(self.discord.add_reaction(channel, message, ReactionEmoji::Unicode(emoji.to_string()))?)
+    }
+
+    fn send_private_message(&self, user: UserId, text: &str) -> Result<MessageId, CommandError> {
+        let channel = self.discord.create_private_channel(user)?;
+        self.send_message(channel.id, text)
+    }
+}
+
+/// Fill in `builder` with the contents of `embed`.
+fn build_embed(builder: EmbedBuilder, embed: &Embed) -> EmbedBuilder {
+    let mut e = builder;
+    if let Some(ref title) = embed.title {
+        e = e.title(title);
+    }
+    if let Some(ref description) = embed.description {
+        e = e.description(description);
+    }
+    if let Some(ref footer) = embed.footer {
+        e = e.footer(|f| f.text(footer));
+    }
+    e.fields(|f| {
+                 let mut fields = f;
+                 for field in &embed.fields {
+                     fields = fields.field(&field.name, &field.value, field.inline);
+                 }
+                 fields
+             })
+}
+
+/// Describe a Discord message using what `state` knows about where it was sent.
+pub fn incoming_message(state: &State, message: &Message) -> IncomingMessage {
+    edited_message(state,
+                   message.id,
+                   message.channel_id,
+                   &message.author,
+                   &message.content)
+}
+
+/// The message a `MessageUpdate` event describes, from the parts of it that
+/// are there.
+pub fn edited_message(state: &State,
+                      id: MessageId,
+                      channel_id: ChannelId,
+                      author: &User,
+                      content: &str)
+                      -> IncomingMessage {
+    let server_id = match state.find_channel(channel_id) {
+        Some(ChannelRef::Public(server, _)) => Some(server.id),
+        _ => None,
+    };
+
+    IncomingMessage {
+        id: id,
+        channel_id: channel_id,
+        server_id: server_id,
+        author: Author {
+            id: author.id,
+            name: author.name.clone(),
+        },
+        member: None,
+        directory: None,
+        content: content.to_string(),
+    }
+}
+
+/// Fill in the author's standing in the server `message` was sent in, and
+/// what's in the server. Only worth doing for messages that are commands.
+pub fn add_server_details(state: &State, message: &mut IncomingMessage) {
+    if let Some(ChannelRef::Public(server, _)) = state.find_channel(message.channel_id) {
+        message.member = Some(ServerCaller::new(server, message.channel_id, message.author.id));
+        message.directory = Some(Directory::new(server));
+    }
+}
@@ -0,0 +1,141 @@
+use framework::pool::Job;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Runs a callback for anything still being watched when its deadline passes.
+pub struct Watchdog {
+    shared: Arc<Shared>,
+}
+
+/// Stops watching when dropped.
+pub struct Watch<'a> {
+    watchdog: &'a Watchdog,
+    id: u64,
+}
+
+struct Shared {
+    watched: Mutex<Watched>,
+    changed: Condvar,
+}
+
+struct Watched {
+    next_id: u64,
+    deadlines: Vec<(u64, Instant, Box<Job>)>,
+    stopped: bool,
+}
+
+impl Watchdog {
+    pub fn new() -> Watchdog {
+        let shared = Arc::new(Shared {
+                                  watched: Mutex::new(Watched {
+                                                          next_id: 0,
+                                                          deadlines: Vec::new(),
+                                                          stopped: false,
+                                                      }),
+                                  changed: Condvar::new(),
+                              });
+        let thread_shared = shared.clone();
+        thread::spawn(move || watch(&thread_shared));
+
+        Watchdog { shared: shared }
+    }
+
+    /// Call `on_timeout` on the watchdog's thread if the returned `Watch` is
+    /// still alive after `timeout`.
+    pub fn watch<F>(&self, timeout: Duration, on_timeout: F) -> Watch
+        where F: FnOnce() + Send + 'static
+    {
+        let mut watched = self.shared.watched.lock().unwrap();
+        let id = watched.next_id;
+        watched.next_id += 1;
+        watched.deadlines.push((id, Instant::now() + timeout, Box::new(on_timeout)));
+        self.shared.changed.notify_one();
+
+        Watch {
+            watchdog: self,
+            id: id,
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.watched.lock().unwrap().stopped = true;
+        self.shared.changed.notify_one();
+    }
+}
+
+impl<'a> Drop for Watch<'a> {
+    fn drop(&mut self) {
+        let mut watched = self.watchdog.shared.watched.lock().unwrap();
+        watched.deadlines.retain(|&(id, _, _)| id != self.id);
+    }
+}
+
+fn watch(shared: &Shared) {
+    let mut watched = shared.watched.lock().unwrap();
+    loop {
+        if watched.stopped {
+            return;
+        }
+
+        let now = Instant::now();
+        let (expired, waiting): (Vec<_>, Vec<_>) =
+            watched.deadlines.drain(..).partition(|&(_, deadline, _)| deadline <= now);
+        watched.deadlines = waiting;
+        if !expired.is_empty() {
+            // Callbacks may take a while, and may drop other watches.
+            drop(watched);
+            for (_, _, on_timeout) in expired {
+                on_timeout.run();
+            }
+            watched = shared.watched.lock().unwrap();
+            continue;
+        }
+
+        watched = match watched.deadlines.iter().map(|&(_, deadline, _)| deadline).min() {
+            Some(next) => shared.changed.wait_timeout(watched, next - now).unwrap().0,
+            None => shared.changed.wait(watched).unwrap(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn calls_back_after_timeout() {
+        let watchdog = Watchdog::new();
+        let (tx, rx) = channel();
+
+        let _watch = watchdog.watch(Duration::from_millis(10), move || tx.send(()).unwrap());
+
+        assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn dropped_watch_never_calls_back() {
+        let watchdog = Watchdog::new();
+        let (tx, rx) = channel();
+
+        drop(watchdog.watch(Duration::from_millis(10), move || tx.send(()).unwrap()));
+
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn earlier_deadlines_first() {
+        let watchdog = Watchdog::new();
+        let (tx, rx) = channel();
+        let tx2 = tx.clone();
+
+        let _slow = watchdog.watch(Duration::from_millis(200), move || tx.send("slow").unwrap());
+        let _fast = watchdog.watch(Duration::from_millis(10), move || tx2.send("fast").unwrap());
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("fast"));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("slow"));
+    }
+}
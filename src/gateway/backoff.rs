@@ -0,0 +1,94 @@
+use rand::{self, Rng};
+use std::cmp;
+use std::time::Duration;
+use util::duration_ms;
+
+/// Exponentially growing delays between reconnect attempts, randomized so a
+/// gateway outage doesn't have every client reconnecting in lockstep.
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Backoff {
+        Backoff {
+            base: base,
+            max: max,
+            attempt: 0,
+        }
+    }
+
+    /// How many delays have been handed out since the last reset.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// The delay before the next attempt.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.delay(self.attempt, rand::thread_rng().next_f64());
+        self.attempt = self.attempt.saturating_add(1);
+        delay
+    }
+
+    /// Start over from the base delay, once a connection has proved stable.
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Between half and all of `base * 2^attempt`, capped at `max`. `jitter`
+    /// is in `[0, 1)` and picks where in that range the delay falls.
+    fn delay(&self, attempt: u32, jitter: f64) -> Duration {
+        let max_ms = duration_ms(self.max);
+        let ceiling_ms = cmp::min(duration_ms(self.base).saturating_mul(1u64 << cmp::min(attempt, 32)),
+                                  max_ms);
+        let half = ceiling_ms / 2;
+        let jittered = half + ((ceiling_ms - half) as f64 * jitter) as u64;
+
+        Duration::from_millis(jittered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backoff() -> Backoff {
+        Backoff::new(Duration::from_secs(1), Duration::from_secs(60))
+    }
+
+    #[test]
+    fn delay_doubles() {
+        let b = backoff();
+
+        assert_eq!(b.delay(0, 0.0), Duration::from_millis(500));
+        assert_eq!(b.delay(1, 0.0), Duration::from_millis(1000));
+        assert_eq!(b.delay(2, 0.0), Duration::from_millis(2000));
+        assert_eq!(b.delay(2, 0.5), Duration::from_millis(3000));
+    }
+
+    #[test]
+    fn delay_is_capped() {
+        let b = backoff();
+
+        assert_eq!(b.delay(10, 0.0), Duration::from_secs(30));
+        assert!(b.delay(10, 0.999) <= Duration::from_secs(60));
+        assert!(b.delay(1000, 0.999) <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn next_delay_grows_and_resets() {
+        let mut b = backoff();
+
+        for attempt in 0..8 {
+            let delay = b.next_delay();
+            assert!(delay >= b.delay(attempt, 0.0));
+            assert!(delay <= b.delay(attempt, 1.0));
+        }
+        assert_eq!(b.attempt(), 8);
+
+        b.reset();
+        assert!(b.next_delay() <= Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod backoff;
+pub mod shards;
+pub mod status;
+pub mod supervisor;
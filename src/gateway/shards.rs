@@ -0,0 +1,57 @@
+use discord::model::ServerId;
+use gateway::status::ConnectionStatus;
+use std::slice;
+use std::sync::Arc;
+
+/// The status of every shard's connection, indexed by shard ID.
+#[derive(Clone, Default)]
+pub struct Shards {
+    statuses: Vec<Arc<ConnectionStatus>>,
+}
+
+impl Shards {
+    pub fn new(count: u8) -> Shards {
+        Shards { statuses: (0..count).map(|_| Arc::new(ConnectionStatus::new())).collect() }
+    }
+
+    /// How many shards there are. Zero when not connected to Discord at all.
+    pub fn count(&self) -> usize {
+        self.statuses.len()
+    }
+
+    pub fn status(&self, shard: usize) -> Option<&Arc<ConnectionStatus>> {
+        self.statuses.get(shard)
+    }
+
+    pub fn iter(&self) -> slice::Iter<Arc<ConnectionStatus>> {
+        self.statuses.iter()
+    }
+
+    /// The shard Discord sends `server`'s events to, if there are any shards.
+    pub fn shard_for(&self, server: ServerId) -> Option<usize> {
+        if self.statuses.is_empty() {
+            return None;
+        }
+
+        Some(((server.0 >> 22) % self.statuses.len() as u64) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shard_for_server() {
+        let shards = Shards::new(4);
+
+        assert_eq!(shards.shard_for(ServerId(41771983423143937)), Some(2));
+        assert_eq!(shards.shard_for(ServerId(0)), Some(0));
+        assert_eq!(shards.shard_for(ServerId(3 << 22)), Some(3));
+    }
+
+    #[test]
+    fn shard_for_without_shards() {
+        assert_eq!(Shards::default().shard_for(ServerId(1)), None);
+    }
+}
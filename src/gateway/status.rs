@@ -0,0 +1,99 @@
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConnectionState {
+    /// Making the first connection.
+    Connecting,
+    /// Receiving events.
+    Connected,
+    /// Waiting to try connecting again after the connection was lost.
+    Reconnecting { attempt: u32 },
+    /// Gave up, or shut down on purpose.
+    Stopped,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConnectionState::Connecting => write!(f, "connecting"),
+            ConnectionState::Connected => write!(f, "connected"),
+            ConnectionState::Reconnecting { attempt } => {
+                write!(f, "reconnecting (attempt {})", attempt)
+            }
+            ConnectionState::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+/// What a gateway connection is doing, shared with whatever wants to report on it.
+pub struct ConnectionStatus {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    state: ConnectionState,
+    since: Instant,
+    resumes: u64,
+    reconnects: u64,
+    servers: usize,
+}
+
+impl ConnectionStatus {
+    pub fn new() -> ConnectionStatus {
+        ConnectionStatus {
+            inner: Mutex::new(Inner {
+                                  state: ConnectionState::Connecting,
+                                  since: Instant::now(),
+                                  resumes: 0,
+                                  reconnects: 0,
+                                  servers: 0,
+                              }),
+        }
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// How long the connection has been in its current state.
+    pub fn state_age(&self) -> Duration {
+        self.inner.lock().unwrap().since.elapsed()
+    }
+
+    /// Sessions picked back up without losing events.
+    pub fn resumes(&self) -> u64 {
+        self.inner.lock().unwrap().resumes
+    }
+
+    /// Sessions started over from scratch after the first.
+    pub fn reconnects(&self) -> u64 {
+        self.inner.lock().unwrap().reconnects
+    }
+
+    /// Servers the connection is receiving events for.
+    pub fn servers(&self) -> usize {
+        self.inner.lock().unwrap().servers
+    }
+
+    pub fn set_state(&self, state: ConnectionState) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.state != state {
+            inner.state = state;
+            inner.since = Instant::now();
+        }
+    }
+
+    pub fn set_servers(&self, servers: usize) {
+        self.inner.lock().unwrap().servers = servers;
+    }
+
+    pub fn record_resume(&self) {
+        self.inner.lock().unwrap().resumes += 1;
+    }
+
+    pub fn record_reconnect(&self) {
+        self.inner.lock().unwrap().reconnects += 1;
+    }
+}
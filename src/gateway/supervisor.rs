@@ -0,0 +1,278 @@
+use discord::{self, Connection, Discord, State};
+use discord::model::{Event, ReadyEvent};
+use gateway::backoff::Backoff;
+use gateway::status::{ConnectionState, ConnectionStatus};
+use logging;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Receive errors in a row, other than the gateway closing, before the
+/// connection is treated as broken.
+const MAX_CONSECUTIVE_ERRORS: u32 = 5;
+
+/// How long a connection has to stay up before reconnecting starts over from
+/// the shortest delay. A gateway that accepts connections only to drop them
+/// right away gets slower and slower attempts instead.
+const STABLE_CONNECTION_SECS: u64 = 60;
+
+/// How often to check whether to stop while no events arrive.
+const STOP_POLL_MS: u64 = 250;
+
+/// Whether the supervisor should keep receiving events.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+    Continue,
+    Stop,
+}
+
+/// Keeps a gateway connection alive, reconnecting with backoff whenever it
+/// drops, and hands every event to a handler along with up to date `State`.
+///
+/// `discord::Connection` already tries to resume the session when the
+/// websocket fails; the supervisor takes over when that isn't enough.
+pub struct Supervisor {
+    discord: Arc<Discord>,
+    /// `(shard ID, total shards)`, or `None` for an unsharded connection.
+    shard: Option<(u8, u8)>,
+    status: Arc<ConnectionStatus>,
+    backoff: Backoff,
+}
+
+impl Supervisor {
+    pub fn new(discord: Arc<Discord>,
+               shard: Option<(u8, u8)>,
+               status: Arc<ConnectionStatus>)
+               -> Supervisor {
+        Supervisor {
+            discord: discord,
+            shard: shard,
+            status: status,
+            backoff: Backoff::new(Duration::from_secs(1), Duration::from_secs(300)),
+        }
+    }
+
+    /// Receive events until `handle` returns `Flow::Stop` or `stop_requested`
+    /// returns true, or the gateway refuses the connection in a way retrying
+    /// won't fix. `stop_requested` is checked even while no events arrive, so
+    /// a quiet connection stops promptly too.
+    pub fn run<F, S>(&mut self, stop_requested: S, mut handle: F) -> Result<(), discord::Error>
+        where F: FnMut(&State, &Event) -> Flow,
+              S: Fn() -> bool
+    {
+        let _fields = logging::scope(self.log_fields());
+        let (connection, ready) = self.connect()?;
+        let mut state = self.connected(ready);
+        let mut events = Events::start(connection);
+        let mut connected_at = Instant::now();
+        let mut consecutive_errors = 0;
+
+        loop {
+            if stop_requested() {
+                self.status.set_state(ConnectionState::Stopped);
+                events.close();
+                return Ok(());
+            }
+
+            let broken = match events.received.recv_timeout(Duration::from_millis(STOP_POLL_MS)) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => {
+                    error!("The thread receiving events stopped");
+                    true
+                }
+                Ok(Ok(event)) => {
+                    consecutive_errors = 0;
+                    if connected_at.elapsed() >= Duration::from_secs(STABLE_CONNECTION_SECS) {
+                        self.backoff.reset();
+                    }
+                    match event {
+                        Event::Ready(ref ready) => {
+                            // The connection re-identified on its own.
+                            self.status.record_reconnect();
+                            state = State::new(ready.clone());
+                        }
+                        Event::Resumed { .. } => {
+                            self.status.record_resume();
+                            state.update(&event);
+                        }
+                        _ => state.update(&event),
+                    }
+                    self.status.set_servers(state.servers().len());
+                    if handle(&state, &event) == Flow::Stop {
+                        self.status.set_state(ConnectionState::Stopped);
+                        events.close();
+                        return Ok(());
+                    }
+                    false
+                }
+                Ok(Err(discord::Error::Closed(code, body))) => {
+                    warn!("Gateway closed the connection with code {:?}: {}", code, body);
+                    if is_fatal_close(code) {
+                        self.status.set_state(ConnectionState::Stopped);
+                        return Err(discord::Error::Closed(code, body));
+                    }
+                    true
+                }
+                Ok(Err(err)) => {
+                    warn!("Receive error: {:?}", err);
+                    consecutive_errors += 1;
+                    consecutive_errors >= MAX_CONSECUTIVE_ERRORS
+                }
+            };
+
+            if broken {
+                consecutive_errors = 0;
+                let (connection, ready) = self.reconnect()?;
+                events = Events::start(connection);
+                state = self.connected(ready);
+                connected_at = Instant::now();
+                self.status.record_reconnect();
+            }
+        }
+    }
+
+    /// Make the first connection, retrying until it works or can't.
+    fn connect(&mut self) -> Result<(Connection, ReadyEvent), discord::Error> {
+        self.status.set_state(ConnectionState::Connecting);
+        loop {
+            match self.open() {
+                Ok(connected) => return Ok(connected),
+                Err(err) => self.retry_after(err)?,
+            }
+        }
+    }
+
+    /// Make a whole new connection, after a backoff delay. Resuming the
+    /// session isn't possible from here: `discord::Connection` only does that
+    /// itself, inside `recv_event`.
+    fn reconnect(&mut self) -> Result<(Connection, ReadyEvent), discord::Error> {
+        self.status.set_state(ConnectionState::Reconnecting { attempt: self.backoff.attempt() + 1 });
+        self.wait();
+        loop {
+            match self.open() {
+                Ok(connected) => return Ok(connected),
+                Err(err) => self.retry_after(err)?,
+            }
+        }
+    }
+
+    /// Give up on `err` if retrying won't help, otherwise wait before the
+    /// next attempt.
+    fn retry_after(&mut self, err: discord::Error) -> Result<(), discord::Error> {
+        error!("Unable to connect to Discord: {}", err);
+        if let discord::Error::Closed(code, _) = err {
+            if is_fatal_close(code) {
+                self.status.set_state(ConnectionState::Stopped);
+                return Err(err);
+            }
+        }
+
+        self.status.set_state(ConnectionState::Reconnecting { attempt: self.backoff.attempt() + 1 });
+        self.wait();
+        Ok(())
+    }
+
+    fn wait(&mut self) {
+        let delay = self.backoff.next_delay();
+        info!("Connecting again in {}.{:03}s",
+              delay.as_secs(),
+              delay.subsec_nanos() / 1_000_000);
+        thread::sleep(delay);
+    }
+
+    fn open(&self) -> Result<(Connection, ReadyEvent), discord::Error> {
+        match self.shard {
+            Some((id, total)) => self.discord.connect_sharded(id, total),
+            None => self.discord.connect(),
+        }
+    }
+
+    /// Added to log lines, saying which shard they're about.
+    fn log_fields(&self) -> Vec<(&'static str, String)> {
+        match self.shard {
+            Some((id, total)) => vec![("shard", format!("{}/{}", id, total))],
+            None => Vec::new(),
+        }
+    }
+
+    fn connected(&mut self, ready: ReadyEvent) -> State {
+        info!("Connected as {} to {} servers",
+              &ready.user.username,
+              &ready.servers.len());
+
+        self.status.set_state(ConnectionState::Connected);
+        State::new(ready)
+    }
+}
+
+/// Events from a connection, received on a thread of its own since
+/// `Connection::recv_event` blocks until there is one.
+struct Events {
+    received: Receiver<Result<Event, discord::Error>>,
+    closing: Arc<AtomicBool>,
+}
+
+impl Events {
+    fn start(connection: Connection) -> Events {
+        let (tx, rx) = mpsc::channel();
+        let closing = Arc::new(AtomicBool::new(false));
+        let thread_closing = closing.clone();
+        let fields = logging::current_fields();
+        thread::spawn(move || {
+            let _fields = logging::scope(fields);
+            let mut connection = connection;
+            loop {
+                let received = connection.recv_event();
+                if thread_closing.load(Ordering::SeqCst) {
+                    if let Err(e) = connection.shutdown() {
+                        debug!("Unable to close the gateway connection cleanly: {}", e);
+                    }
+                    return;
+                }
+                // Nobody is listening once the connection was given up on.
+                if tx.send(received).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Events {
+            received: rx,
+            closing: closing,
+        }
+    }
+
+    /// Close the connection once the event being waited for arrives. If the
+    /// bot exits first, the connection closes along with it.
+    fn close(&self) {
+        self.closing.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Gateway close codes that mean the bot is misconfigured, so reconnecting
+/// would only fail again.
+fn is_fatal_close(code: Option<u16>) -> bool {
+    match code {
+        // Authentication failed, invalid shard, sharding required, invalid
+        // API version, invalid or disallowed intents.
+        Some(4004) | Some(4010) | Some(4011) | Some(4012) | Some(4013) | Some(4014) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fatal_close_codes() {
+        assert!(is_fatal_close(Some(4004)));
+        assert!(is_fatal_close(Some(4011)));
+        assert!(!is_fatal_close(Some(1000)));
+        assert!(!is_fatal_close(Some(4000)));
+        assert!(!is_fatal_close(Some(4009)));
+        assert!(!is_fatal_close(None));
+    }
+}
@@ -0,0 +1,23 @@
+extern crate chrono;
+extern crate discord;
+#[macro_use]
+extern crate log;
+extern crate rand;
+extern crate reqwest;
+extern crate regex;
+extern crate scraper;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate tiny_http;
+extern crate toml;
+
+pub mod commands;
+pub mod config;
+pub mod framework;
+pub mod gateway;
+pub mod logging;
+pub mod metrics;
+pub mod storage;
+pub mod util;
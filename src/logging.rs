@@ -0,0 +1,310 @@
+//! Leveled logging on top of the `log` crate.
+//!
+//! Lines go to stderr, either as text for people or as JSON objects, one per
+//! line, for log collectors. Besides its message, each line carries the
+//! fields of whatever its thread is in the middle of, such as the command
+//! being run; see `scope`.
+
+use chrono::Utc;
+use log::{self, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde_json::{self, Map, Value};
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+thread_local!(static FIELDS: RefCell<Vec<(&'static str, String)>> = RefCell::new(Vec::new()));
+
+/// How log lines are written.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// `<time> <level> <module>: <message> key=value ...`
+    Human,
+    /// One JSON object per line, with fields as keys.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "human" => Ok(Format::Human),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("must be \"human\" or \"json\", not {:?}.", s)),
+        }
+    }
+}
+
+/// Which levels are logged, overall and per module. Written like
+/// `info,technobot::gateway=debug,hyper=warn`: a bare level is the default,
+/// and `module=level` applies to that module and everything inside it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    pub fn parse(spec: &str) -> Result<Filter, String> {
+        let mut filter = Filter {
+            default: LevelFilter::Info,
+            modules: Vec::new(),
+        };
+
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let mut parts = directive.splitn(2, '=');
+            let first = parts.next().unwrap().trim();
+            match parts.next() {
+                Some(level) => {
+                    if first.is_empty() {
+                        return Err(format!("{:?} doesn't name a module.", directive));
+                    }
+                    filter.modules.push((first.to_string(), parse_level(level.trim())?));
+                }
+                None => filter.default = parse_level(first)?,
+            }
+        }
+
+        // Most specific first, so the first match wins.
+        filter.modules.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        Ok(filter)
+    }
+
+    /// The most detailed level logged for `target`, a module path.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        for &(ref module, level) in &self.modules {
+            if target == module || target.starts_with(&format!("{}::", module)) {
+                return level;
+            }
+        }
+
+        self.default
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|&(_, level)| level).fold(self.default, ::std::cmp::max)
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level.parse().map_err(|_| {
+        format!("{:?} isn't a log level. Use off, error, warn, info, debug or trace.",
+                level)
+    })
+}
+
+pub struct Logger {
+    filter: Filter,
+    format: Format,
+    out: Mutex<Box<Write + Send>>,
+}
+
+impl Logger {
+    pub fn new(filter: Filter, format: Format, out: Box<Write + Send>) -> Logger {
+        Logger {
+            filter: filter,
+            format: format,
+            out: Mutex::new(out),
+        }
+    }
+
+    fn line(&self, record: &Record) -> String {
+        let time = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ");
+        FIELDS.with(|fields| {
+            let fields = fields.borrow();
+            match self.format {
+                Format::Human => {
+                    let mut line = format!("{} {:<5} {}: {}",
+                                           time,
+                                           record.level(),
+                                           record.target(),
+                                           record.args());
+                    for &(name, ref value) in fields.iter() {
+                        line.push_str(&format!(" {}={}", name, HumanValue(value)));
+                    }
+                    line
+                }
+                Format::Json => {
+                    let mut object = Map::new();
+                    for &(name, ref value) in fields.iter() {
+                        object.insert(name.to_string(), Value::String(value.clone()));
+                    }
+                    object.insert("time".to_string(), Value::String(time.to_string()));
+                    object.insert("level".to_string(),
+                                  Value::String(record.level().to_string()));
+                    object.insert("target".to_string(),
+                                  Value::String(record.target().to_string()));
+                    object.insert("message".to_string(),
+                                  Value::String(record.args().to_string()));
+                    serde_json::to_string(&object).unwrap()
+                }
+            }
+        })
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = self.line(record);
+        let mut out = self.out.lock().unwrap();
+        // There's nowhere left to report a failure to log.
+        let _ = writeln!(out, "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = self.out.lock().unwrap().flush();
+    }
+}
+
+/// Log to stderr for the rest of the program. Fails if a logger is already
+/// set.
+pub fn init(filter: Filter, format: Format) -> Result<(), SetLoggerError> {
+    log::set_max_level(filter.max_level());
+    log::set_boxed_logger(Box::new(Logger::new(filter, format, Box::new(io::stderr()))))
+}
+
+/// Fields added by `scope`, removed again when it's dropped.
+pub struct Scope {
+    previous_len: usize,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        FIELDS.with(|fields| fields.borrow_mut().truncate(self.previous_len));
+    }
+}
+
+/// Add `fields` to every line this thread logs until the returned `Scope` is
+/// dropped.
+pub fn scope(fields: Vec<(&'static str, String)>) -> Scope {
+    FIELDS.with(|current| {
+        let mut current = current.borrow_mut();
+        let previous_len = current.len();
+        current.extend(fields);
+        Scope { previous_len: previous_len }
+    })
+}
+
+/// The fields this thread is logging with, to carry them over to another.
+pub fn current_fields() -> Vec<(&'static str, String)> {
+    FIELDS.with(|fields| fields.borrow().clone())
+}
+
+/// Quotes values that would otherwise run into the next field.
+struct HumanValue<'a>(&'a str);
+
+impl<'a> fmt::Display for HumanValue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() || self.0.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+            write!(f, "{:?}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+    use std::sync::Arc;
+
+    /// Collects what a `Logger` writes.
+    #[derive(Clone)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn log_one(format: Format, level: Level, target: &str, message: &str) -> String {
+        let output = Output(Arc::new(Mutex::new(Vec::new())));
+        let logger = Logger::new(Filter::parse("trace").unwrap(), format, Box::new(output.clone()));
+        logger.log(&Record::builder()
+                        .args(format_args!("{}", message))
+                        .level(level)
+                        .target(target)
+                        .build());
+        let written = output.0.lock().unwrap().clone();
+        String::from_utf8(written).unwrap()
+    }
+
+    #[test]
+    fn filter_defaults_to_info() {
+        let filter = Filter::parse("").unwrap();
+
+        assert_eq!(filter.level_for("technobot"), LevelFilter::Info);
+    }
+
+    #[test]
+    fn filter_uses_most_specific_module() {
+        let filter = Filter::parse("warn, technobot=info, technobot::gateway=trace").unwrap();
+
+        assert_eq!(filter.level_for("hyper::client"), LevelFilter::Warn);
+        assert_eq!(filter.level_for("technobot::commands::ffxiv"), LevelFilter::Info);
+        assert_eq!(filter.level_for("technobot::gateway::supervisor"), LevelFilter::Trace);
+        assert_eq!(filter.level_for("technobotanist"), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn filter_rejects_unknown_levels() {
+        assert!(Filter::parse("loud").is_err());
+        assert!(Filter::parse("technobot=loud").is_err());
+        assert!(Filter::parse("=info").is_err());
+    }
+
+    #[test]
+    fn human_lines_include_fields() {
+        let _fields = scope(vec![("command", "ffxiv events".to_string()), ("user", "2".to_string())]);
+
+        let line = log_one(Format::Human, Level::Warn, "technobot::dispatcher", "Failed");
+
+        assert!(line.ends_with(" WARN  technobot::dispatcher: Failed command=\"ffxiv events\" \
+                                user=2\n"),
+                "{}",
+                line);
+    }
+
+    #[test]
+    fn json_lines_include_fields() {
+        let _fields = scope(vec![("command", "ping".to_string())]);
+
+        let line = log_one(Format::Json, Level::Info, "technobot", "Ran \"ping\"");
+        let object: Map<String, Value> = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(object["level"], "INFO");
+        assert_eq!(object["target"], "technobot");
+        assert_eq!(object["message"], "Ran \"ping\"");
+        assert_eq!(object["command"], "ping");
+        assert!(line.ends_with("}\n"));
+    }
+
+    #[test]
+    fn scope_removes_its_fields() {
+        let _outer = scope(vec![("server", "1".to_string())]);
+        {
+            let _inner = scope(vec![("command", "ping".to_string())]);
+            assert_eq!(current_fields().len(), 2);
+        }
+
+        assert_eq!(current_fields(), vec![("server", "1".to_string())]);
+    }
+}
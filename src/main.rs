@@ -1,147 +1,315 @@
-extern crate chrono;
+extern crate ctrlc;
 extern crate discord;
 extern crate dotenv;
 #[macro_use]
-extern crate nom;
-extern crate rand;
-extern crate reqwest;
-extern crate regex;
-extern crate scraper;
-extern crate serde;
-#[macro_use]
-extern crate serde_derive;
-extern crate serde_json;
-
-mod commands;
-mod util;
-mod framework;
+extern crate log;
+extern crate technobot;
 
-use discord::Discord;
-use discord::model::{Event, Message};
+use discord::{Discord, State};
+use discord::model::{Event, UserId};
 use dotenv::dotenv;
-use framework::args::Args;
+use technobot::commands;
+use technobot::config::Config;
+use technobot::framework::dispatcher::Dispatcher;
+use technobot::framework::pool::WorkerPool;
+use technobot::framework::prefixes::Prefixes;
+use technobot::framework::shutdown::{ShutdownKind, ShutdownRequest};
+use technobot::framework::registry::CommandRegistry;
+use technobot::framework::transport::{self, DiscordResponder, IncomingMessage};
+use technobot::framework::console::{self, ConsoleResponder};
+use technobot::logging;
+use technobot::metrics;
+use technobot::gateway::supervisor::{Flow, Supervisor};
+use technobot::gateway::shards::Shards;
+use technobot::storage::{FileBackend, Storage, migrations};
+use std::cmp;
 use std::env;
+use std::io;
+use std::process::{self, Command};
+use std::sync::{Arc, mpsc};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::u8;
 
-type Exec = Fn(&Discord, &Message, Args);
-
-struct BotCommand {
-    command: String,
-    description: Option<String>,
-    exec: Box<Exec>,
-}
-
-impl BotCommand {
-    fn command_matches(&self, message: &str) -> bool {
-        message.starts_with(&self.command)
-    }
-}
-
-macro_rules! command (
-    ($cmd_name:expr, $fn_name:path) => (
-        {
-            BotCommand {
-                command: format!("{}", &$cmd_name).to_lowercase(),
-                description: None,
-                exec: Box::new($fn_name),
-            }
-        }
-    );
-    ($cmd_name:expr, $desc:expr, $fn_name:path) => (
-        {
-            BotCommand {
-                command: format!("{}", &$cmd_name).to_lowercase(),
-                description: Some($desc.to_string()),
-                exec: Box::new($fn_name),
-            }
-        }
-    );
-);
+/// How long shutting down waits for running commands to finish and shards
+/// to disconnect.
+const SHUTDOWN_DEADLINE_SECS: u64 = 30;
 
 fn main() {
     dotenv().ok();
 
-    let bot_command_prefix = env::var("TECHNOBOT_PREFIX").unwrap_or("!".to_string());
+    let console_mode = env::args().skip(1).any(|a| a == "--console");
+    let check_config = env::args().skip(1).any(|a| a == "--check-config");
+
+    let config = match Config::load().and_then(|c| c.validate(!console_mode).map(|_| c)) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    if check_config {
+        println!("Configuration OK.");
+        return;
+    }
+    // Validation made sure the filter parses.
+    logging::init(config.log_filter().unwrap(), config.log_format).unwrap();
 
-    let mut commands: Vec<BotCommand> = Vec::new();
-    commands.push(command!("ping", commands::misc::ping));
-    commands.push(command!("8-ball",
-                           "Ask the magic 8-ball any yes/no question.",
-                           commands::misc::eight_ball));
-    commands.push(command!("roll", commands::misc::roll));
+    let mut registry = CommandRegistry::new();
+    commands::register_all(&mut registry, &config);
+    // Console mode is for trying things out, so it leaves saved state alone.
+    let storage = if console_mode {
+        Storage::in_memory()
+    } else {
+        let backend = FileBackend::new(&config.storage_file);
+        match Storage::open(Box::new(backend), &migrations::all()) {
+            Ok(s) => s,
+            Err(e) => panic!("Unable to open {}: {}", config.storage_file.display(), e),
+        }
+    };
+    let storage = Arc::new(storage);
+    let prefixes = Prefixes::with_storage(&config.prefix, storage.clone());
+    let mut owners: Vec<UserId> = config.owners.iter().map(|&id| UserId(id)).collect();
+    if console_mode {
+        // Whoever is at the console is running the bot.
+        owners.push(console::CONSOLE_USER);
+    } else if owners.is_empty() {
+        warn!("No owners configured; owner-only commands are disabled.");
+    }
+    let mut dispatcher = Dispatcher::new(registry, prefixes, owners);
+    dispatcher.set_storage(storage);
 
-    commands.push(command!("ffxiv resets",
-                           "Show how long until the daily/weekly resets in FF XIV",
-                           commands::ffxiv::resets));
-    commands.push(command!("ffxiv events",
-                           "List known events in FF XIV",
-                           commands::ffxiv::events));
+    if console_mode {
+        println!("Console mode. Type commands as you would in a private message, e.g. {}help. \
+                  End input to quit.",
+                 dispatcher.prefixes().default_prefix());
+        let stdin = io::stdin();
+        let responder = Arc::new(ConsoleResponder::new(io::stdout()));
+        if let Err(e) = console::run(&dispatcher, stdin.lock(), responder) {
+            panic!("Unable to read from the console: {}", e);
+        }
+        // Reaching the end of the input is as good as asking to stop.
+        dispatcher.shutdown().request(ShutdownRequest::new(ShutdownKind::Stop));
+        stop(&dispatcher);
+        return;
+    }
 
-    let discord = match Discord::from_bot_token(&env::var("DISCORD_TOKEN").expect("Could not read DISCORD_TOKEN environment variable")) {
+    // Validation made sure there's a token when connecting to Discord.
+    let token = config.token.as_ref().unwrap();
+    let discord = match Discord::from_bot_token(token) {
         Ok(d) => d,
         Err(e) => panic!("Unable to log in to Discord: {}", e),
     };
 
-    let recommended_shards = match discord.suggested_shard_count() {
-        Ok(s) => s,
-        Err(e) => panic!("Could not get recommended shard count: {}", e),
+    let shard_count = match config.shards {
+        Some(n) => n,
+        None => {
+            let recommended_shards = match discord.suggested_shard_count() {
+                Ok(s) => s,
+                Err(e) => panic!("Could not get recommended shard count: {}", e),
+            };
+            info!("Recommended number of shards for bot: {}", &recommended_shards);
+            if recommended_shards > u8::MAX as u64 {
+                panic!("Discord recommends {} shards, more than the {} supported.",
+                       recommended_shards,
+                       u8::MAX);
+            }
+            cmp::max(recommended_shards as u8, 1)
+        }
     };
-    println!("Recommended number of shards for bot: {}",
-             &recommended_shards);
 
-    let (mut connection, ready_event) = match discord.connect() {
-        Ok((mut c, r)) => (c, r),
-        Err(e) => panic!("Unable to connect to Discord: {}", e),
-    };
+    let shards = Shards::new(shard_count);
+    dispatcher.set_shards(shards.clone());
+    if let Some(ref address) = config.http_listen {
+        match metrics::server::spawn(address, dispatcher.metrics().clone(), shards.clone()) {
+            Ok(bound) => info!("Serving /metrics and /healthz on http://{}", bound),
+            Err(e) => panic!("Unable to listen on {}: {}", address, e),
+        }
+    }
+    let dispatcher = Arc::new(dispatcher);
+    let discord = Arc::new(discord);
+    let workers = config.workers;
+    // Commands run off the gateway threads, so a slow one can't hold up
+    // receiving events. Messages arriving while every worker is busy and the
+    // queue is full are dropped.
+    let pool = Arc::new(WorkerPool::new(workers, workers * 16));
 
-    println!("Connected as: {}", &ready_event.user.username);
-    match &ready_event.shard {
-        &Some(s) => println!("Connected as shard: {:?}", s),
-        &None => println!("Not using sharding."),
+    {
+        let dispatcher = dispatcher.clone();
+        let handled = ctrlc::set_handler(move || {
+            let request = ShutdownRequest::new(ShutdownKind::Stop);
+            if dispatcher.shutdown().request(request) {
+                info!("Shutting down once running commands finish. Interrupt again to stop \
+                       right away.");
+            } else {
+                process::exit(1);
+            }
+        });
+        if let Err(e) = handled {
+            warn!("Unable to handle signals, so they'll stop the bot abruptly: {}", e);
+        }
     }
-    println!("Connected servers: {}", &ready_event.servers.len());
-
-    loop {
-        match connection.recv_event() {
-            Ok(Event::MessageCreate(message)) => {
-                if message.author.id != ready_event.user.id {
-                    if message.content.starts_with(&bot_command_prefix) {
-                        let lowercase_message = message.content[bot_command_prefix.len()..]
-                            .to_lowercase();
-                        for cmd in &commands {
-                            if cmd.command_matches(&lowercase_message) {
-                                (cmd.exec)(&discord,
-                                           &message,
-                                           Args::new(message.content[bot_command_prefix.len() +
-                                                     cmd.command.len()..]
-                                                             .trim()));
-                                break;
-                            }
-                        }
-                    }
 
-                    if message.content == format!("{}help", &bot_command_prefix) {
-                        for cmd in &commands {
-                            let cmd_help = match &cmd.description {
-                                &Some(ref d) => {
-                                    format!("{}{} -> {}", &bot_command_prefix, &cmd.command, d)
-                                }
-                                &None => format!("{}{}", &bot_command_prefix, &cmd.command),
-                            };
-                            let _ = discord.send_message(message.channel_id, &cmd_help, "", false);
+    // Every shard runs until the bot shuts down, and a shard that can't stay
+    // connected shuts the bot down.
+    let (stopped_tx, stopped_rx) = mpsc::channel();
+    for (id, status) in shards.iter().enumerate() {
+        let shard = if shard_count == 1 {
+            None
+        } else {
+            Some((id as u8, shard_count))
+        };
+        let mut supervisor = Supervisor::new(discord.clone(), shard, status.clone());
+        let discord = discord.clone();
+        let dispatcher = dispatcher.clone();
+        let pool = pool.clone();
+        let stopped_tx = stopped_tx.clone();
+        thread::spawn(move || {
+            let _stop = StopOnExit(dispatcher.clone());
+            let stop_requested = || dispatcher.shutdown().is_requested();
+            let result = supervisor.run(stop_requested, |state, event| {
+                match *event {
+                    Event::MessageCreate(ref message) if message.author.id != state.user().id => {
+                        let responder = Arc::new(DiscordResponder::new(discord.clone(), state.user().id));
+                        let mut incoming = transport::incoming_message(state, message);
+                        add_server_details(&dispatcher, state, &mut incoming);
+                        let dispatcher = dispatcher.clone();
+                        if pool.try_execute(move || dispatcher.dispatch(responder, &incoming)).is_err() {
+                            warn!("Too busy to handle message {}; dropping it.", message.id);
                         }
                     }
-                    if message.content == format!("{}quit", &bot_command_prefix) {
-                        println!("Quitting.");
-                        break;
+                    // Edits that only change embeds (like link previews) come
+                    // without content, and aren't worth running again. Nor
+                    // are updates to messages that were never edited, like
+                    // pinning them.
+                    Event::MessageUpdate {
+                        id,
+                        channel_id,
+                        content: Some(ref content),
+                        author: Some(ref author),
+                        edited_timestamp: Some(_),
+                        ..
+                    } if author.id != state.user().id => {
+                        let responder = Arc::new(DiscordResponder::new(discord.clone(), state.user().id));
+                        let mut incoming = transport::edited_message(state, id, channel_id, author, content);
+                        add_server_details(&dispatcher, state, &mut incoming);
+                        let dispatcher = dispatcher.clone();
+                        if pool.try_execute(move || dispatcher.dispatch_edit(responder, &incoming)).is_err() {
+                            warn!("Too busy to handle edit of message {}; dropping it.", id);
+                        }
                     }
+                    _ => {}
+                }
+                if dispatcher.shutdown().is_requested() {
+                    Flow::Stop
+                } else {
+                    Flow::Continue
                 }
+            });
+            let _ = stopped_tx.send((id, result));
+        });
+    }
+    // Only the shards can say they've stopped.
+    drop(stopped_tx);
+
+    let request = dispatcher.shutdown().wait();
+    let deadline = Instant::now() + Duration::from_secs(SHUTDOWN_DEADLINE_SECS);
+    dispatcher.finish_shutdown(&request, until(deadline));
+    let failed = join_shards(&stopped_rx, shard_count as usize, deadline);
+    if request.kind == ShutdownKind::Restart {
+        info!("Restarting.");
+        restart();
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Look up who sent `message` and what's in their server, but only when it's
+/// a command: most messages aren't, and this runs on the shard's thread.
+fn add_server_details(dispatcher: &Dispatcher, state: &State, message: &mut IncomingMessage) {
+    if dispatcher.is_addressed(message, state.user().id) {
+        transport::add_server_details(state, message);
+    }
+}
+
+/// Wait until `deadline` for every shard to stop, which they do soon after a
+/// shutdown is requested unless they're waiting to reconnect. Returns whether
+/// any stopped because it couldn't stay connected.
+fn join_shards(stopped: &Receiver<(usize, Result<(), discord::Error>)>,
+               count: usize,
+               deadline: Instant)
+               -> bool {
+    let mut failed = false;
+    for joined in 0..count {
+        match stopped.recv_timeout(until(deadline)) {
+            Ok((id, Err(e))) => {
+                error!("Shard {} was unable to stay connected to Discord: {}", id, e);
+                failed = true;
             }
-            Ok(_) => {}
-            Err(discord::Error::Closed(code, body)) => {
-                println!("Gateway closed on us with code {:?}: {}", code, body);
+            Ok((_, Ok(()))) => {}
+            Err(RecvTimeoutError::Timeout) => {
+                warn!("Stopping with {} of {} shards still connected.", count - joined, count);
                 break;
             }
-            Err(err) => println!("Receive error: {:?}", err),
+            // The rest panicked, and have nothing to close.
+            Err(RecvTimeoutError::Disconnected) => break,
         }
     }
+
+    failed
+}
+
+/// How long until `deadline`, or no time at all if it's passed.
+fn until(deadline: Instant) -> Duration {
+    let now = Instant::now();
+    if deadline > now {
+        deadline - now
+    } else {
+        Duration::from_secs(0)
+    }
+}
+
+/// Shuts the bot down when a shard's thread ends, even by panicking, so the
+/// bot never runs on without it.
+struct StopOnExit(Arc<Dispatcher>);
+
+impl Drop for StopOnExit {
+    fn drop(&mut self) {
+        self.0.shutdown().request(ShutdownRequest::new(ShutdownKind::Stop));
+    }
+}
+
+/// Finish shutting down, then exit or restart as requested.
+fn stop(dispatcher: &Dispatcher) {
+    let request = dispatcher.shutdown().wait();
+    dispatcher.finish_shutdown(&request, Duration::from_secs(SHUTDOWN_DEADLINE_SECS));
+    if request.kind == ShutdownKind::Restart {
+        info!("Restarting.");
+        restart();
+    }
+}
+
+/// Replace this process with a fresh copy of the bot, run the same way.
+#[cfg(unix)]
+fn restart() {
+    use std::os::unix::process::CommandExt;
+
+    let error = match env::current_exe() {
+        Ok(exe) => Command::new(exe).args(env::args_os().skip(1)).exec(),
+        Err(e) => e,
+    };
+    error!("Unable to restart: {}", error);
+    process::exit(1);
+}
+
+#[cfg(not(unix))]
+fn restart() {
+    let started = env::current_exe()
+        .and_then(|exe| Command::new(exe).args(env::args_os().skip(1)).spawn());
+    if let Err(e) = started {
+        error!("Unable to restart: {}", e);
+        process::exit(1);
+    }
 }
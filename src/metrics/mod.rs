@@ -0,0 +1,330 @@
+//! Counters and histograms describing what the bot has been doing, written
+//! out in the Prometheus text format.
+
+pub mod server;
+
+use gateway::shards::Shards;
+use gateway::status::ConnectionState;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+use util::duration_ms;
+
+/// Upper bounds, in seconds, of the command latency histogram's buckets.
+const LATENCY_BUCKETS: &'static [f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+                                           5.0, 10.0, 30.0];
+
+/// Everything the bot counts. Gateway metrics aren't here: they're read from
+/// the shards' `ConnectionStatus` when rendering.
+pub struct Metrics {
+    commands: Counter,
+    command_duration: Histogram,
+    upstream_errors: Counter,
+    cache_lookups: Counter,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            commands: Counter::new("technobot_commands_total",
+                                   "Commands run, by how they turned out.",
+                                   &["command", "outcome"]),
+            command_duration: Histogram::new("technobot_command_duration_seconds",
+                                             "How long commands took to run.",
+                                             &["command"],
+                                             LATENCY_BUCKETS),
+            upstream_errors: Counter::new("technobot_upstream_errors_total",
+                                          "Failed requests to services outside Discord.",
+                                          &["upstream"]),
+            cache_lookups: Counter::new("technobot_cache_lookups_total",
+                                        "Lookups in caches of upstream data, by whether they \
+                                         hit.",
+                                        &["cache", "result"]),
+        }
+    }
+
+    /// `command` ran for `duration`, and `outcome` is `"ok"` or the kind of
+    /// error it ended with.
+    pub fn record_command(&self, command: &str, outcome: &str, duration: Duration) {
+        self.commands.inc(&[command, outcome]);
+        self.command_duration.observe(&[command], duration_ms(duration) as f64 / 1000.0);
+    }
+
+    pub fn record_upstream_error(&self, upstream: &str) {
+        self.upstream_errors.inc(&[upstream]);
+    }
+
+    pub fn record_cache_lookup(&self, cache: &str, hit: bool) {
+        self.cache_lookups.inc(&[cache, if hit { "hit" } else { "miss" }]);
+    }
+
+    pub fn commands_run(&self, command: &str, outcome: &str) -> u64 {
+        self.commands.get(&[command, outcome])
+    }
+
+    /// Every metric, including the state of each shard's gateway connection.
+    pub fn render(&self, shards: &Shards) -> String {
+        let mut out = String::new();
+        self.commands.render(&mut out);
+        self.command_duration.render(&mut out);
+        self.upstream_errors.render(&mut out);
+        self.cache_lookups.render(&mut out);
+        render_gateway(shards, &mut out);
+        out
+    }
+}
+
+fn render_gateway(shards: &Shards, out: &mut String) {
+    let connected = Counter::new("technobot_gateway_connected",
+                                 "Whether the shard is connected to the gateway.",
+                                 &["shard"]);
+    let servers = Counter::new("technobot_gateway_servers",
+                               "Servers the shard receives events for.",
+                               &["shard"]);
+    let reconnects = Counter::new("technobot_gateway_reconnects_total",
+                                  "Gateway sessions started over after the first.",
+                                  &["shard"]);
+    let resumes = Counter::new("technobot_gateway_resumes_total",
+                               "Gateway sessions resumed without losing events.",
+                               &["shard"]);
+    for (id, status) in shards.iter().enumerate() {
+        let id = id.to_string();
+        let shard = [id.as_str()];
+        let is_connected = status.state() == ConnectionState::Connected;
+        connected.add(&shard, if is_connected { 1 } else { 0 });
+        servers.add(&shard, status.servers() as u64);
+        reconnects.add(&shard, status.reconnects());
+        resumes.add(&shard, status.resumes());
+    }
+
+    connected.render_as(out, "gauge");
+    servers.render_as(out, "gauge");
+    reconnects.render(out);
+    resumes.render(out);
+}
+
+/// A count for each combination of label values. Also rendered as a gauge
+/// for values that are read fresh each time.
+struct Counter {
+    name: &'static str,
+    help: &'static str,
+    label_names: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl Counter {
+    fn new(name: &'static str,
+           help: &'static str,
+           label_names: &'static [&'static str])
+           -> Counter {
+        Counter {
+            name: name,
+            help: help,
+            label_names: label_names,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn inc(&self, labels: &[&str]) {
+        self.add(labels, 1);
+    }
+
+    fn add(&self, labels: &[&str], n: u64) {
+        assert_eq!(labels.len(), self.label_names.len(), "wrong labels for {}", self.name);
+        let key = labels.iter().map(|l| l.to_string()).collect();
+        *self.values.lock().unwrap().entry(key).or_insert(0) += n;
+    }
+
+    fn get(&self, labels: &[&str]) -> u64 {
+        let key: Vec<String> = labels.iter().map(|l| l.to_string()).collect();
+        self.values.lock().unwrap().get(&key).cloned().unwrap_or(0)
+    }
+
+    fn render(&self, out: &mut String) {
+        self.render_as(out, "counter")
+    }
+
+    fn render_as(&self, out: &mut String, kind: &str) {
+        header(out, self.name, self.help, kind);
+        for (values, count) in self.values.lock().unwrap().iter() {
+            let _ = writeln!(out, "{}{} {}", self.name, labels(self.label_names, values, None), count);
+        }
+    }
+}
+
+/// How many observations fell at or below each bucket's bound, for each
+/// combination of label values.
+struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    label_names: &'static [&'static str],
+    buckets: &'static [f64],
+    values: Mutex<BTreeMap<Vec<String>, Observations>>,
+}
+
+#[derive(Default)]
+struct Observations {
+    /// Not cumulative; summed when rendering.
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(name: &'static str,
+           help: &'static str,
+           label_names: &'static [&'static str],
+           buckets: &'static [f64])
+           -> Histogram {
+        Histogram {
+            name: name,
+            help: help,
+            label_names: label_names,
+            buckets: buckets,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn observe(&self, labels: &[&str], value: f64) {
+        assert_eq!(labels.len(), self.label_names.len(), "wrong labels for {}", self.name);
+        let key = labels.iter().map(|l| l.to_string()).collect();
+        let mut values = self.values.lock().unwrap();
+        let observations = values.entry(key).or_insert_with(|| {
+            Observations { buckets: vec![0; self.buckets.len()], ..Observations::default() }
+        });
+        if let Some(i) = self.buckets.iter().position(|&bound| value <= bound) {
+            observations.buckets[i] += 1;
+        }
+        observations.sum += value;
+        observations.count += 1;
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "histogram");
+        for (values, observations) in self.values.lock().unwrap().iter() {
+            let mut cumulative = 0;
+            for (bound, count) in self.buckets.iter().zip(&observations.buckets) {
+                cumulative += *count;
+                let le = bound.to_string();
+                let _ = writeln!(out,
+                                 "{}_bucket{} {}",
+                                 self.name,
+                                 labels(self.label_names, values, Some(&le)),
+                                 cumulative);
+            }
+            let _ = writeln!(out,
+                             "{}_bucket{} {}",
+                             self.name,
+                             labels(self.label_names, values, Some("+Inf")),
+                             observations.count);
+            let _ = writeln!(out,
+                             "{}_sum{} {}",
+                             self.name,
+                             labels(self.label_names, values, None),
+                             observations.sum);
+            let _ = writeln!(out,
+                             "{}_count{} {}",
+                             self.name,
+                             labels(self.label_names, values, None),
+                             observations.count);
+        }
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// `{name="value",...}`, with `le` added for histogram buckets.
+fn labels(names: &[&str], values: &[String], le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = names.iter()
+        .zip(values)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_commands_by_outcome() {
+        let metrics = Metrics::new();
+
+        metrics.record_command("ping", "ok", Duration::from_millis(3));
+        metrics.record_command("ping", "ok", Duration::from_millis(3));
+        metrics.record_command("roll", "bad_arguments", Duration::from_millis(1));
+
+        assert_eq!(metrics.commands_run("ping", "ok"), 2);
+        assert_eq!(metrics.commands_run("roll", "ok"), 0);
+        let text = metrics.render(&Shards::default());
+        assert!(text.contains("# TYPE technobot_commands_total counter\n"));
+        assert!(text.contains("technobot_commands_total{command=\"ping\",outcome=\"ok\"} 2\n"));
+        assert!(text.contains("technobot_commands_total{command=\"roll\",\
+                               outcome=\"bad_arguments\"} 1\n"));
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let metrics = Metrics::new();
+
+        metrics.record_command("ffxiv events", "ok", Duration::from_millis(40));
+        metrics.record_command("ffxiv events", "ok", Duration::from_millis(2000));
+        metrics.record_command("ffxiv events", "ok", Duration::from_secs(60));
+
+        let text = metrics.render(&Shards::default());
+        let bucket = |le: &str| {
+            format!("technobot_command_duration_seconds_bucket{{command=\"ffxiv events\",\
+                     le=\"{}\"}}",
+                    le)
+        };
+        assert!(text.contains(&format!("{} 0\n", bucket("0.025"))));
+        assert!(text.contains(&format!("{} 1\n", bucket("0.05"))));
+        assert!(text.contains(&format!("{} 2\n", bucket("2.5"))));
+        assert!(text.contains(&format!("{} 2\n", bucket("30"))));
+        assert!(text.contains(&format!("{} 3\n", bucket("+Inf"))));
+        assert!(text.contains("technobot_command_duration_seconds_sum{command=\"ffxiv events\"} \
+                               62.04\n"));
+        assert!(text.contains("technobot_command_duration_seconds_count{command=\"ffxiv \
+                               events\"} 3\n"));
+    }
+
+    #[test]
+    fn renders_gateway_status() {
+        let shards = Shards::new(2);
+        shards.status(1).unwrap().set_state(ConnectionState::Connected);
+        shards.status(1).unwrap().record_reconnect();
+
+        let text = Metrics::new().render(&shards);
+
+        assert!(text.contains("# TYPE technobot_gateway_connected gauge\n"));
+        assert!(text.contains("technobot_gateway_connected{shard=\"0\"} 0\n"));
+        assert!(text.contains("technobot_gateway_connected{shard=\"1\"} 1\n"));
+        assert!(text.contains("technobot_gateway_reconnects_total{shard=\"1\"} 1\n"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let metrics = Metrics::new();
+
+        metrics.record_cache_lookup("a \"quoted\"\\cache", true);
+
+        assert!(metrics.render(&Shards::default())
+                    .contains("cache=\"a \\\"quoted\\\"\\\\cache\",result=\"hit\"} 1\n"));
+    }
+}
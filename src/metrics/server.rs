@@ -0,0 +1,124 @@
+//! A small HTTP server for monitoring: `/metrics` for Prometheus to scrape,
+//! and `/healthz` for whatever restarts the bot when it's stuck.
+
+use gateway::shards::Shards;
+use gateway::status::ConnectionState;
+use metrics::Metrics;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const PROMETHEUS_TEXT: &'static str = "text/plain; version=0.0.4";
+const PLAIN_TEXT: &'static str = "text/plain; charset=utf-8";
+
+/// Listen on `address` in the background, returning the address actually
+/// bound (useful when `address` asks for any free port).
+pub fn spawn(address: &str, metrics: Arc<Metrics>, shards: Shards) -> Result<SocketAddr, String> {
+    let server = Server::http(address).map_err(|e| e.to_string())?;
+    let bound = server.server_addr();
+    thread::spawn(move || for request in server.incoming_requests() {
+                      handle(request, &metrics, &shards);
+                  });
+
+    Ok(bound)
+}
+
+fn handle(request: Request, metrics: &Metrics, shards: &Shards) {
+    let (status, content_type, body) = respond(request.method(), request.url(), metrics, shards);
+    let header = Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap();
+    let response = Response::from_string(body).with_status_code(status).with_header(header);
+    if let Err(e) = request.respond(response) {
+        debug!("Unable to answer a monitoring request: {}", e);
+    }
+}
+
+/// The status code, content type and body answering a request.
+fn respond(method: &Method,
+           url: &str,
+           metrics: &Metrics,
+           shards: &Shards)
+           -> (u16, &'static str, String) {
+    let path = url.split('?').next().unwrap_or("");
+    match (method, path) {
+        (&Method::Get, "/metrics") => (200, PROMETHEUS_TEXT, metrics.render(shards)),
+        (&Method::Get, "/healthz") => {
+            let (healthy, body) = health(shards);
+            (if healthy { 200 } else { 503 }, PLAIN_TEXT, body)
+        }
+        (_, "/metrics") |
+        (_, "/healthz") => (405, PLAIN_TEXT, "Only GET is supported.\n".to_string()),
+        _ => (404, PLAIN_TEXT, "Not found. Try /metrics or /healthz.\n".to_string()),
+    }
+}
+
+/// Healthy when every shard is connected to the gateway, along with a line
+/// saying what each one is doing.
+fn health(shards: &Shards) -> (bool, String) {
+    if shards.count() == 0 {
+        return (false, "Not connected to Discord.\n".to_string());
+    }
+
+    let mut healthy = true;
+    let mut body = String::new();
+    for (id, status) in shards.iter().enumerate() {
+        let state = status.state();
+        healthy = healthy && state == ConnectionState::Connected;
+        body.push_str(&format!("shard {}: {}\n", id, state));
+    }
+    (healthy, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    #[test]
+    fn healthy_only_when_every_shard_is_connected() {
+        let shards = Shards::new(2);
+        shards.status(0).unwrap().set_state(ConnectionState::Connected);
+        shards.status(1).unwrap().set_state(ConnectionState::Reconnecting { attempt: 2 });
+
+        let (status, _, body) = respond(&Method::Get, "/healthz", &Metrics::new(), &shards);
+        assert_eq!(status, 503);
+        assert_eq!(body, "shard 0: connected\nshard 1: reconnecting (attempt 2)\n");
+
+        shards.status(1).unwrap().set_state(ConnectionState::Connected);
+        let (status, _, _) = respond(&Method::Get, "/healthz", &Metrics::new(), &shards);
+        assert_eq!(status, 200);
+    }
+
+    #[test]
+    fn unhealthy_without_shards() {
+        let (status, _, _) = respond(&Method::Get, "/healthz", &Metrics::new(), &Shards::default());
+
+        assert_eq!(status, 503);
+    }
+
+    #[test]
+    fn unknown_paths_and_methods() {
+        let metrics = Metrics::new();
+        let shards = Shards::default();
+
+        assert_eq!(respond(&Method::Get, "/", &metrics, &shards).0, 404);
+        assert_eq!(respond(&Method::Post, "/metrics", &metrics, &shards).0, 405);
+        assert_eq!(respond(&Method::Get, "/metrics?x=1", &metrics, &shards).0, 200);
+    }
+
+    #[test]
+    fn serves_metrics_over_http() {
+        let metrics = Arc::new(Metrics::new());
+        metrics.record_command("ping", "ok", Default::default());
+        let address = spawn("127.0.0.1:0", metrics, Shards::default()).unwrap();
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.0\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.0 200"), "{}", response);
+        assert!(response.contains("technobot_commands_total{command=\"ping\",outcome=\"ok\"} 1"));
+    }
+}
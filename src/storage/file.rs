@@ -0,0 +1,76 @@
+use serde_json;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use storage::{Backend, Snapshot, StorageError};
+
+/// Saves snapshots as a JSON file.
+pub struct FileBackend {
+    path: PathBuf,
+}
+
+impl FileBackend {
+    pub fn new(path: &Path) -> FileBackend {
+        FileBackend { path: path.to_path_buf() }
+    }
+}
+
+impl Backend for FileBackend {
+    /// A missing file is treated as nothing having been saved yet.
+    fn load(&self) -> Result<Snapshot, StorageError> {
+        match File::open(&self.path) {
+            Ok(f) => Ok(serde_json::from_reader(f)?),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(Snapshot::default()),
+            Err(e) => Err(StorageError::Io(e)),
+        }
+    }
+
+    fn save(&self, snapshot: &Snapshot) -> Result<(), StorageError> {
+        // Write next to the real file and rename over it, so a crash mid-write
+        // can't leave a truncated file behind.
+        let tmp_path = self.path.with_extension("tmp");
+        {
+            let f = File::create(&tmp_path)?;
+            serde_json::to_writer_pretty(f, snapshot)?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::env;
+    use std::process;
+    use storage::Table;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("technobot-{}-{}.json", name, process::id()))
+    }
+
+    #[test]
+    fn load_missing_file() {
+        let backend = FileBackend::new(&temp_path("missing-storage"));
+
+        assert_eq!(backend.load().unwrap(), Snapshot::default());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = temp_path("saved-storage");
+        let backend = FileBackend::new(&path);
+        let mut snapshot = Snapshot::default();
+        snapshot.version = 3;
+        let mut table = Table::new();
+        table.insert("1".to_string(), Value::String("?".to_string()));
+        snapshot.tables.insert("prefixes".to_string(), table);
+
+        backend.save(&snapshot).unwrap();
+        let loaded = FileBackend::new(&path).load().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, snapshot);
+    }
+}
@@ -0,0 +1,29 @@
+use std::sync::Mutex;
+use storage::{Backend, Snapshot, StorageError};
+
+/// Keeps saved snapshots in memory, for tests.
+pub struct MemoryBackend {
+    saved: Mutex<Snapshot>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> MemoryBackend {
+        MemoryBackend::with_snapshot(Snapshot::default())
+    }
+
+    /// A backend that starts out having saved `snapshot`.
+    pub fn with_snapshot(snapshot: Snapshot) -> MemoryBackend {
+        MemoryBackend { saved: Mutex::new(snapshot) }
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn load(&self) -> Result<Snapshot, StorageError> {
+        Ok(self.saved.lock().unwrap().clone())
+    }
+
+    fn save(&self, snapshot: &Snapshot) -> Result<(), StorageError> {
+        *self.saved.lock().unwrap() = snapshot.clone();
+        Ok(())
+    }
+}
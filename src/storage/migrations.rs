@@ -0,0 +1,111 @@
+use storage::{Snapshot, StorageError};
+
+/// One step in bringing stored data up to date with the code.
+pub struct Migration {
+    /// Migrations run in order of version, each at most once.
+    pub version: u32,
+    pub description: &'static str,
+    run: Box<Fn(&mut Snapshot) -> Result<(), String> + Send + Sync>,
+}
+
+impl Migration {
+    pub fn new<F>(version: u32, description: &'static str, run: F) -> Migration
+        where F: Fn(&mut Snapshot) -> Result<(), String> + Send + Sync + 'static
+    {
+        Migration {
+            version: version,
+            description: description,
+            run: Box::new(run),
+        }
+    }
+}
+
+/// The bot's migrations, oldest first. There are none yet: data has only
+/// ever been stored the way it is now.
+pub fn all() -> Vec<Migration> {
+    Vec::new()
+}
+
+/// Run the migrations `snapshot` hasn't had yet. Either all of them are
+/// applied or, if one fails, none are. Returns whether anything changed.
+pub fn apply(snapshot: &mut Snapshot, migrations: &[Migration]) -> Result<bool, StorageError> {
+    let mut migrated = snapshot.clone();
+    let mut last_version = 0;
+    for migration in migrations {
+        if migration.version <= last_version {
+            return Err(StorageError::Migration(migration.version,
+                                               "Migrations are out of order".to_string()));
+        }
+        last_version = migration.version;
+        if migration.version <= migrated.version {
+            continue;
+        }
+
+        info!("Migrating storage to version {}: {}",
+              migration.version,
+              migration.description);
+        (migration.run)(&mut migrated).map_err(|e| StorageError::Migration(migration.version, e))?;
+        migrated.version = migration.version;
+    }
+
+    if migrated == *snapshot {
+        return Ok(false);
+    }
+    *snapshot = migrated;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::Table;
+
+    fn add_table(version: u32, name: &'static str) -> Migration {
+        Migration::new(version, "Add a table", move |snapshot| {
+            snapshot.tables.insert(name.to_string(), Table::new());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn applies_pending_migrations() {
+        let mut snapshot = Snapshot::default();
+        snapshot.version = 1;
+        let migrations = vec![add_table(1, "one"), add_table(2, "two"), add_table(3, "three")];
+
+        assert!(apply(&mut snapshot, &migrations).unwrap());
+        assert_eq!(snapshot.version, 3);
+        assert!(!snapshot.tables.contains_key("one"));
+        assert!(snapshot.tables.contains_key("two"));
+        assert!(snapshot.tables.contains_key("three"));
+    }
+
+    #[test]
+    fn nothing_to_apply() {
+        let mut snapshot = Snapshot::default();
+        snapshot.version = 2;
+
+        assert!(!apply(&mut snapshot, &[add_table(1, "one"), add_table(2, "two")]).unwrap());
+        assert!(snapshot.tables.is_empty());
+    }
+
+    #[test]
+    fn failed_migration_changes_nothing() {
+        let mut snapshot = Snapshot::default();
+        let migrations = vec![add_table(1, "one"),
+                              Migration::new(2, "Fail", |_| Err("nope".to_string()))];
+
+        match apply(&mut snapshot, &migrations) {
+            Err(StorageError::Migration(2, _)) => {}
+            other => panic!("expected migration 2 to fail, got {:?}", other),
+        }
+        assert_eq!(snapshot, Snapshot::default());
+    }
+
+    #[test]
+    fn out_of_order_migrations() {
+        let mut snapshot = Snapshot::default();
+
+        assert!(apply(&mut snapshot, &[add_table(2, "two"), add_table(1, "one")]).is_err());
+    }
+}
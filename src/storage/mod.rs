@@ -0,0 +1,290 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::sync::{Arc, RwLock};
+
+pub mod file;
+pub mod memory;
+pub mod migrations;
+
+pub use self::file::FileBackend;
+pub use self::memory::MemoryBackend;
+pub use self::migrations::Migration;
+
+/// One kind of record, keyed by string.
+pub type Table = BTreeMap<String, Value>;
+
+/// Everything stored, as saved by a backend.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The last migration applied.
+    pub version: u32,
+    pub tables: BTreeMap<String, Table>,
+}
+
+/// Somewhere to keep a `Snapshot` between runs.
+pub trait Backend: Send + Sync {
+    /// What was last saved, or an empty snapshot if nothing has been.
+    fn load(&self) -> Result<Snapshot, StorageError>;
+
+    fn save(&self, snapshot: &Snapshot) -> Result<(), StorageError>;
+}
+
+/// Lets something else keep hold of the backend `Storage` uses.
+impl<B: Backend + ?Sized> Backend for Arc<B> {
+    fn load(&self) -> Result<Snapshot, StorageError> {
+        (**self).load()
+    }
+
+    fn save(&self, snapshot: &Snapshot) -> Result<(), StorageError> {
+        (**self).save(snapshot)
+    }
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(io::Error),
+    /// Stored data didn't match the type it was read as, or couldn't be written.
+    Serialization(serde_json::Error),
+    /// A migration failed, leaving storage at the previous version.
+    Migration(u32, String),
+}
+
+impl StdError for StorageError {
+    fn description(&self) -> &str {
+        match self {
+            &StorageError::Io(_) => "Storage I/O error",
+            &StorageError::Serialization(_) => "Storage serialization error",
+            &StorageError::Migration(..) => "Storage migration failed",
+        }
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &StorageError::Io(ref e) => write!(f, "Storage I/O error: {}", e),
+            &StorageError::Serialization(ref e) => write!(f, "Storage serialization error: {}", e),
+            &StorageError::Migration(version, ref m) => {
+                write!(f, "Storage migration {} failed: {}", version, m)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(e: io::Error) -> Self {
+        StorageError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(e: serde_json::Error) -> Self {
+        StorageError::Serialization(e)
+    }
+}
+
+/// Bot state that outlives the process. Everything is held in memory and
+/// written through to the backend on every change.
+pub struct Storage {
+    backend: Box<Backend>,
+    snapshot: RwLock<Snapshot>,
+}
+
+impl Storage {
+    /// Load what `backend` has saved and bring it up to date with `migrations`.
+    pub fn open(backend: Box<Backend>, migrations: &[Migration]) -> Result<Storage, StorageError> {
+        let mut snapshot = backend.load()?;
+        if migrations::apply(&mut snapshot, migrations)? {
+            backend.save(&snapshot)?;
+        }
+
+        Ok(Storage {
+               backend: backend,
+               snapshot: RwLock::new(snapshot),
+           })
+    }
+
+    /// Storage that only lasts as long as the process, for tests and console mode.
+    pub fn in_memory() -> Storage {
+        Storage {
+            backend: Box::new(MemoryBackend::new()),
+            snapshot: RwLock::new(Snapshot::default()),
+        }
+    }
+
+    /// The last migration applied.
+    pub fn version(&self) -> u32 {
+        self.snapshot.read().unwrap().version
+    }
+
+    /// Save everything again. Changes are already saved as they're made, so
+    /// this only matters if a save failed earlier.
+    pub fn flush(&self) -> Result<(), StorageError> {
+        self.backend.save(&self.snapshot.read().unwrap())
+    }
+
+    fn get(&self, table: &str, key: &str) -> Option<Value> {
+        let snapshot = self.snapshot.read().unwrap();
+        snapshot.tables.get(table).and_then(|t| t.get(key)).cloned()
+    }
+
+    fn all(&self, table: &str) -> Table {
+        let snapshot = self.snapshot.read().unwrap();
+        snapshot.tables.get(table).cloned().unwrap_or_default()
+    }
+
+    /// Change `table` with `f` and save the result.
+    fn update<F, R>(&self, table: &str, f: F) -> Result<R, StorageError>
+        where F: FnOnce(&mut Table) -> R
+    {
+        let mut snapshot = self.snapshot.write().unwrap();
+        let result = f(snapshot.tables.entry(table.to_string()).or_insert_with(Table::new));
+        self.backend.save(&snapshot)?;
+        Ok(result)
+    }
+}
+
+/// Typed access to one table of `Storage`.
+pub struct Repository<T> {
+    storage: Arc<Storage>,
+    table: &'static str,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Repository<T> {
+    fn clone(&self) -> Repository<T> {
+        Repository {
+            storage: self.storage.clone(),
+            table: self.table,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Repository<T> {
+    pub fn new(storage: Arc<Storage>, table: &'static str) -> Repository<T> {
+        Repository {
+            storage: storage,
+            table: table,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<T>, StorageError> {
+        match self.storage.get(self.table, key) {
+            Some(value) => Ok(Some(serde_json::from_value(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Every record in the table, ordered by key.
+    pub fn all(&self) -> Result<Vec<(String, T)>, StorageError> {
+        self.storage
+            .all(self.table)
+            .into_iter()
+            .map(|(key, value)| Ok((key, serde_json::from_value(value)?)))
+            .collect()
+    }
+
+    pub fn put(&self, key: &str, value: &T) -> Result<(), StorageError> {
+        let value = serde_json::to_value(value)?;
+        self.storage.update(self.table, |table| {
+            table.insert(key.to_string(), value);
+        })
+    }
+
+    /// Remove the record for `key`, returning whether there was one.
+    pub fn remove(&self, key: &str) -> Result<bool, StorageError> {
+        self.storage.update(self.table, |table| table.remove(key).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reminder {
+        text: String,
+        at: u64,
+    }
+
+    fn reminders() -> Repository<Reminder> {
+        Repository::new(Arc::new(Storage::in_memory()), "reminders")
+    }
+
+    fn reminder(text: &str, at: u64) -> Reminder {
+        Reminder {
+            text: text.to_string(),
+            at: at,
+        }
+    }
+
+    #[test]
+    fn put_and_get() {
+        let repo = reminders();
+        repo.put("1", &reminder("stretch", 10)).unwrap();
+
+        assert_eq!(repo.get("1").unwrap(), Some(reminder("stretch", 10)));
+        assert_eq!(repo.get("2").unwrap(), None);
+    }
+
+    #[test]
+    fn put_replaces() {
+        let repo = reminders();
+        repo.put("1", &reminder("stretch", 10)).unwrap();
+        repo.put("1", &reminder("drink water", 20)).unwrap();
+
+        assert_eq!(repo.get("1").unwrap(), Some(reminder("drink water", 20)));
+    }
+
+    #[test]
+    fn remove() {
+        let repo = reminders();
+        repo.put("1", &reminder("stretch", 10)).unwrap();
+
+        assert!(repo.remove("1").unwrap());
+        assert!(!repo.remove("1").unwrap());
+        assert_eq!(repo.get("1").unwrap(), None);
+    }
+
+    #[test]
+    fn all_in_key_order() {
+        let repo = reminders();
+        repo.put("b", &reminder("second", 2)).unwrap();
+        repo.put("a", &reminder("first", 1)).unwrap();
+
+        assert_eq!(repo.all().unwrap(),
+                   vec![("a".to_string(), reminder("first", 1)),
+                        ("b".to_string(), reminder("second", 2))]);
+    }
+
+    #[test]
+    fn tables_are_separate() {
+        let storage = Arc::new(Storage::in_memory());
+        let names: Repository<String> = Repository::new(storage.clone(), "names");
+        let counts: Repository<u32> = Repository::new(storage.clone(), "counts");
+        names.put("1", &"one".to_string()).unwrap();
+
+        assert_eq!(counts.get("1").unwrap(), None);
+    }
+
+    #[test]
+    fn wrong_type_is_an_error() {
+        let storage = Arc::new(Storage::in_memory());
+        let names: Repository<String> = Repository::new(storage.clone(), "things");
+        let counts: Repository<u32> = Repository::new(storage.clone(), "things");
+        names.put("1", &"one".to_string()).unwrap();
+
+        match counts.get("1") {
+            Err(StorageError::Serialization(_)) => {}
+            other => panic!("expected a serialization error, got {:?}", other),
+        }
+    }
+}
@@ -1,135 +1,378 @@
-use nom::{AsBytes, AsChar, ErrorKind, IResult, InputIter, InputLength, is_space, Needed, Slice,
-          space};
-
-use std::ops::{Range, RangeFrom, RangeTo};
-use std::str;
-use std::string::String;
-
-fn non_quote_special<T>(input: T) -> IResult<T, T>
-    where T: Slice<Range<usize>> + Slice<RangeFrom<usize>> + Slice<RangeTo<usize>>,
-          T: InputIter + InputLength,
-          <T as InputIter>::Item: AsChar
-{
-    let input_length = input.input_len();
-    if input_length == 0 {
-        return IResult::Incomplete(Needed::Unknown);
-    }
-
-    for (idx, item) in input.iter_indices() {
-        let item_char = item.as_char();
-        if item_char == '"' || item_char == '\\' {
-            if idx == 0 {
-                return IResult::Error(error_position!(ErrorKind::IsNot, input));
-            } else {
-                return IResult::Done(input.slice(idx..), input.slice(0..idx));
+//! Splitting what users type after a command into values.
+//!
+//! A value is a bare word, or text in quotes to include spaces: straight or
+//! curly, double or single, since phones tend to curl quotes as they're
+//! typed. Inside quotes a backslash escapes the next character, and `\n`,
+//! `\t` and `\u{1F600}` stand for a newline, a tab and any unicode character.
+//! Text in backticks, like a Discord code span, is taken exactly as written,
+//! and so are bare words.
+//!
+//! Single quotes are also apostrophes, as in `'tis` or `'90s`, so quotes
+//! other than `"` only start a quoted value when a matching quote closes it.
+//! Otherwise they're part of a bare word, as are mismatched ones like `“foo"`.
+//!
+//! Positions in errors are byte offsets into the text being parsed.
+
+use std::char;
+use std::fmt;
+
+/// Quotes that can surround a value, as (opening, closing).
+const QUOTES: &'static [(char, char)] = &[('"', '"'),
+                                          ('\'', '\''),
+                                          ('\u{201C}', '\u{201D}'),
+                                          ('\u{2018}', '\u{2019}')];
+
+/// Why some text couldn't be split into values, and where.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub position: usize,
+    pub reason: Reason,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reason {
+    /// The quote at the position was never closed.
+    UnterminatedQuote(char),
+    /// The backticks at the position were never closed.
+    UnterminatedCode,
+    /// The escape at the position isn't one, like `\u{zz}`.
+    BadEscape,
+    /// A closing quote is followed by more text instead of a space.
+    ExpectedSpace,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Reason::UnterminatedQuote(quote) => write!(f, "This {} is never closed.", quote),
+            &Reason::UnterminatedCode => write!(f, "These backticks are never closed."),
+            &Reason::BadEscape => {
+                write!(f,
+                       "That isn't an escape I know. Try `\\n`, `\\t` or `\\u{{...}}` with a \
+                        hex code.")
             }
+            &Reason::ExpectedSpace => write!(f, "Expected a space after the closing quote."),
         }
     }
-    IResult::Done(input.slice(input_length..), input)
 }
 
-fn single<'a>(input: &'a [u8]) -> IResult<&'a [u8], &'a str> {
-    if input.input_len() == 0 {
-        return IResult::Incomplete(Needed::Unknown);
+type Result<T> = ::std::result::Result<T, ParseError>;
+
+/// The next value in `input`, and the rest of `input` after it, or `None` if
+/// there's nothing but whitespace left.
+pub fn next_value(input: &str) -> Result<Option<(String, &str)>> {
+    let start = input.len() - input.trim_left().len();
+    let text = &input[start..];
+    let first = match text.chars().next() {
+        Some(c) => c,
+        None => return Ok(None),
+    };
+
+    let parsed = if first == '`' {
+        code_span(text)
+    } else if let Some(&(open, close)) = QUOTES.iter().find(|&&(open, _)| open == first) {
+        if open == '"' || is_closed(text, open, close) {
+            quoted(text, open, close)
+        } else {
+            // More likely an apostrophe than a quote.
+            Ok(bare_word(text))
+        }
+    } else {
+        Ok(bare_word(text))
+    };
+    let (value, len) = parsed.map_err(|e| offset(e, start))?;
+
+    let rest = end_of_value(&text[len..]).map_err(|e| offset(e, start + len))?;
+    Ok(Some((value, rest)))
+}
+
+/// Whether the quote at the start of `text` is closed by the end of a value:
+/// a `close` that isn't escaped, followed by a space or nothing.
+fn is_closed(text: &str, open: char, close: char) -> bool {
+    let mut chars = text[open.len_utf8()..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == close {
+            let after = open.len_utf8() + i + c.len_utf8();
+            return end_of_value(&text[after..]).is_ok();
+        }
     }
 
-    map_res!(input,
-             do_parse!(
-        arg: take_till!(is_space) >>
-        (arg)
-    ),
-             str::from_utf8)
+    false
+}
+
+/// The word at the start of `text`, up to the next whitespace, and its length.
+fn bare_word(text: &str) -> (String, usize) {
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    (text[..end].to_string(), end)
 }
 
-fn single_string<'a>(input: &'a [u8]) -> IResult<&'a [u8], String> {
-    match single(input) {
-        IResult::Done(r, s) => IResult::Done(r, s.to_string()),
-        IResult::Error(e) => IResult::Error(e),
-        IResult::Incomplete(i) => IResult::Incomplete(i),
+/// The value inside the quotes at the start of `text`, with escapes replaced,
+/// and how many bytes it took up, quotes included.
+fn quoted(text: &str, open: char, close: char) -> Result<(String, usize)> {
+    let unterminated = ParseError {
+        position: 0,
+        reason: Reason::UnterminatedQuote(open),
+    };
+    let mut value = String::new();
+    let mut pos = open.len_utf8();
+    while let Some(c) = text[pos..].chars().next() {
+        let escape_at = pos;
+        pos += c.len_utf8();
+        if c == close {
+            return Ok((value, pos));
+        }
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+
+        let escaped = match text[pos..].chars().next() {
+            Some(e) => e,
+            None => return Err(unterminated),
+        };
+        pos += escaped.len_utf8();
+        match escaped {
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            'u' => {
+                let bad_escape = ParseError {
+                    position: escape_at,
+                    reason: Reason::BadEscape,
+                };
+                let (c, len) = unicode_escape(&text[pos..]).ok_or(bad_escape)?;
+                value.push(c);
+                pos += len;
+            }
+            // Quotes, backslashes and anything else stand for themselves.
+            other => value.push(other),
+        }
     }
+
+    Err(unterminated)
 }
 
-pub fn single_arg<'a>(input: &'a [u8]) -> IResult<&'a [u8], &'a str> {
-    if input.input_len() == 0 {
-        return IResult::Incomplete(Needed::Unknown);
+/// The character a `{1F600}` escape stands for, and how long the escape is.
+fn unicode_escape(text: &str) -> Option<(char, usize)> {
+    if !text.starts_with('{') {
+        return None;
+    }
+    let end = text.find('}')?;
+    let hex = &text[1..end];
+    if hex.is_empty() || hex.len() > 6 {
+        return None;
     }
 
-    do_parse!(input,
-        arg: call!(single) >>
-        alt!(eof!() | space) >>
-        (arg)
-    )
+    let c = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)?;
+    Some((c, end + 1))
 }
 
+/// The text between the backticks at the start of `text`, however many there
+/// are, and how many bytes it took up, backticks included. Code blocks (three
+/// backticks) usually start and end on their own lines, and those line breaks
+/// aren't part of the value.
+fn code_span(text: &str) -> Result<(String, usize)> {
+    let ticks = text.len() - text.trim_left_matches('`').len();
+    let fence = &text[..ticks];
+    match text[ticks..].find(fence) {
+        Some(len) => {
+            let mut value = &text[ticks..ticks + len];
+            if ticks >= 3 {
+                value = value.trim_matches('\n');
+            }
+            Ok((value.to_string(), ticks + len + ticks))
+        }
+        None => {
+            Err(ParseError {
+                    position: 0,
+                    reason: Reason::UnterminatedCode,
+                })
+        }
+    }
+}
 
-fn quoted<'a>(input: &'a [u8]) -> IResult<&'a [u8], String> {
-    map_res!(input,
-             do_parse!(
-        arg: delimited!(
-            char!('"'),
-            escaped_transform!(
-                call!(non_quote_special),
-                '\\',
-                alt!(
-                    tag!("\\") => { |_| &b"\\"[..] } |
-                    tag!("\"") => { |_| &b"\""[..] }
-                )),
-            char!('"')) >>
-        (arg)
-    ),
-             String::from_utf8)
+/// An argument given by name rather than position.
+#[derive(Debug, PartialEq)]
+pub enum Named {
+    /// `--name`, or `--name=value`.
+    Long(String, Option<String>),
+    /// `-n`.
+    Short(char),
 }
 
-pub fn single_quoted_arg<'a>(input: &'a [u8]) -> IResult<&'a [u8], String> {
-    do_parse!(input,
-        arg: alt!(call!(quoted) | call!(single_string)) >>
-        alt!(eof!() | space) >>
-        (arg)
-    )
+/// The named argument at the start of `input`, and the rest of `input` after
+/// it, or `None` if the next thing in `input` isn't one. `--` on its own
+/// isn't one: it conventionally marks the end of named arguments. Nor is a
+/// dash followed by a digit, so negative numbers can be given as values.
+pub fn named_arg(input: &str) -> Result<Option<(Named, &str)>> {
+    let start = input.len() - input.trim_left().len();
+    let text = &input[start..];
+
+    if text.starts_with("--") {
+        let body = &text[2..];
+        let end = body.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(body.len());
+        let name = &body[..end];
+        if name.is_empty() {
+            return Ok(None);
+        }
+
+        let after = &body[end..];
+        if after.starts_with('=') {
+            let value_start = start + 2 + end + 1;
+            let value_text = &after[1..];
+            if value_text.is_empty() || value_text.starts_with(char::is_whitespace) {
+                let named = Named::Long(name.to_string(), Some(String::new()));
+                return Ok(Some((named, value_text)));
+            }
+            return match next_value(value_text).map_err(|e| offset(e, value_start))? {
+                Some((value, rest)) => Ok(Some((Named::Long(name.to_string(), Some(value)), rest))),
+                None => unreachable!("values after `=` were checked for"),
+            };
+        }
+        return match end_of_value(after) {
+            Ok(rest) => Ok(Some((Named::Long(name.to_string(), None), rest))),
+            Err(_) => Ok(None),
+        };
+    }
+
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some('-'), Some(c)) if c.is_alphabetic() => {
+            match end_of_value(&text[1 + c.len_utf8()..]) {
+                Ok(rest) => Ok(Some((Named::Short(c), rest))),
+                Err(_) => Ok(None),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The rest of `text` after the end of a value, with any whitespace skipped.
+fn end_of_value(text: &str) -> Result<&str> {
+    let rest = text.trim_left();
+    if rest.len() == text.len() && !text.is_empty() {
+        return Err(ParseError {
+                       position: 0,
+                       reason: Reason::ExpectedSpace,
+                   });
+    }
+
+    Ok(rest)
+}
+
+fn offset(error: ParseError, by: usize) -> ParseError {
+    ParseError { position: error.position + by, ..error }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn done<'a>(value: &str, rest: &'a str) -> Result<Option<(String, &'a str)>> {
+        Ok(Some((value.to_string(), rest)))
+    }
+
+    fn error<T>(position: usize, reason: Reason) -> Result<T> {
+        Err(ParseError {
+                position: position,
+                reason: reason,
+            })
+    }
+
     #[test]
-    fn test_single_arg() {
-        assert_eq!(single_arg("".as_bytes()),
-                   IResult::Incomplete(Needed::Unknown));
-        assert_eq!(single_arg("single".as_bytes()),
-                   IResult::Done("".as_bytes(), "single"));
-        assert_eq!(single_arg("one two".as_bytes()),
-                   IResult::Done("two".as_bytes(), "one"));
-        assert_eq!(single_arg("\"one two\"".as_bytes()),
-                   IResult::Done("two\"".as_bytes(), "\"one"));
+    fn test_next_value() {
+        assert_eq!(next_value(""), Ok(None));
+        assert_eq!(next_value("  "), Ok(None));
+        assert_eq!(next_value("single"), done("single", ""));
+        assert_eq!(next_value("one two"), done("one", "two"));
+        assert_eq!(next_value("  one\n  two"), done("one", "two"));
+
+        assert_eq!(next_value("\"one two\""), done("one two", ""));
+        assert_eq!(next_value("\"quoted arg\" with more"), done("quoted arg", "with more"));
+        assert_eq!(next_value("\"quoted with \\\" escaped\" more"),
+                   done("quoted with \" escaped", "more"));
+    }
+
+    #[test]
+    fn positioned_errors() {
+        assert_eq!(next_value("  \"quote does not end"),
+                   error(2, Reason::UnterminatedQuote('"')));
+        assert_eq!(next_value("\"no\"space"), error(4, Reason::ExpectedSpace));
+        assert_eq!(next_value("'a \\u{zz}'"), error(3, Reason::BadEscape));
+        assert_eq!(next_value(" `code"), error(1, Reason::UnterminatedCode));
+        assert_eq!(named_arg(" --kind=\"oops"), error(8, Reason::UnterminatedQuote('"')));
     }
 
     #[test]
-    fn test_single_quoted_arg() {
-        assert_eq!(single_quoted_arg("".as_bytes()),
-                   IResult::Incomplete(Needed::Size(1)));
-        assert_eq!(single_quoted_arg("single".as_bytes()),
-                   IResult::Done("".as_bytes(), "single".to_string()));
-        assert_eq!(single_quoted_arg("one two".as_bytes()),
-                   IResult::Done("two".as_bytes(), "one".to_string()));
-
-        assert_eq!(single_quoted_arg("\"quoted arg\" with more".as_bytes()),
-                   IResult::Done("with more".as_bytes(), "quoted arg".to_string()));
-        assert_eq!(single_quoted_arg("\"quoted with \\\" escaped\" more".as_bytes()),
-                   IResult::Done("more".as_bytes(), "quoted with \" escaped".to_string()));
-        assert_eq!(single_quoted_arg("\"quote does not end".as_bytes()),
-                   IResult::Incomplete(Needed::Size(20)));
+    fn quote_styles() {
+        assert_eq!(next_value("'one two' x"), done("one two", "x"));
+        assert_eq!(next_value("\u{201C}one two\u{201D} x"), done("one two", "x"));
+        assert_eq!(next_value("\u{2018}it's\u{2019}"), done("it's", ""));
+        assert_eq!(next_value("\"it's\""), done("it's", ""));
+        // Apostrophes inside words don't start quotes.
+        assert_eq!(next_value("don't x"), done("don't", "x"));
+        assert_eq!(next_value("don\u{2019}t"), done("don\u{2019}t", ""));
     }
 
     #[test]
-    fn test_quoted() {
-        assert_eq!(quoted(&b""[..]), IResult::Incomplete(Needed::Size(1)));
+    fn unclosed_quotes_other_than_double_start_words() {
+        assert_eq!(next_value("'tis so"), done("'tis", "so"));
+        assert_eq!(next_value("the '90s"), done("the", "'90s"));
+        assert_eq!(next_value("'90s"), done("'90s", ""));
+        assert_eq!(next_value("'tis Ed's x"), done("'tis", "Ed's x"));
+        assert_eq!(next_value("\u{2018}90s x"), done("\u{2018}90s", "x"));
+        assert_eq!(next_value("\u{201C}foo\" x"), done("\u{201C}foo\"", "x"));
+        // Words are taken as typed, backslashes and all.
+        assert_eq!(next_value("'C:\\users x"), done("'C:\\users", "x"));
+        assert_eq!(next_value("'tis\\u{zz}"), done("'tis\\u{zz}", ""));
+        assert_eq!(next_value("\"foo\u{201D} x"),
+                   error(0, Reason::UnterminatedQuote('"')));
+    }
 
-        assert_eq!(quoted(&b"\"quoted thing\" lkj lkj lkj lkj lkj lkj "[..]),
-                   IResult::Done(&b" lkj lkj lkj lkj lkj lkj "[..],
-                                 "quoted thing".to_string()));
+    #[test]
+    fn escapes() {
+        assert_eq!(next_value("\"a\\nb\\tc\""), done("a\nb\tc", ""));
+        assert_eq!(next_value("\"\\u{1F600} \\u{e9}\""), done("\u{1F600} \u{e9}", ""));
+        assert_eq!(next_value("'\\\\ \\' \\q'"), done("\\ ' q", ""));
+        assert_eq!(next_value("\u{201C}\\\u{201D}\u{201D}"), done("\u{201D}", ""));
+        assert!(next_value("\"\\u{110000}\"").is_err());
+        assert!(next_value("\"\\u1F600\"").is_err());
+        // Bare words are taken as typed.
+        assert_eq!(next_value("C:\\temp"), done("C:\\temp", ""));
+    }
 
-        assert_eq!(quoted(&b"no quotes here"[..]),
-                   IResult::Error(error_position!(ErrorKind::Char, "no quotes here".as_bytes())));
+    #[test]
+    fn code_spans() {
+        assert_eq!(next_value("`a \\n \"b` c"), done("a \\n \"b", "c"));
+        assert_eq!(next_value("``a ` b`` c"), done("a ` b", "c"));
+        assert_eq!(next_value("```\nfn main() {}\n```"), done("fn main() {}", ""));
+    }
+
+    #[test]
+    fn test_named_arg() {
+        let long = |name: &str, value: Option<&str>| {
+            Named::Long(name.to_string(), value.map(|v| v.to_string()))
+        };
+
+        assert_eq!(named_arg("--upcoming"), Ok(Some((long("upcoming", None), ""))));
+        assert_eq!(named_arg(" --kind maintenance"),
+                   Ok(Some((long("kind", None), "maintenance"))));
+        assert_eq!(named_arg("--kind=\"event rising\" --upcoming"),
+                   Ok(Some((long("kind", Some("event rising")), "--upcoming"))));
+        assert_eq!(named_arg("--kind=\u{201C}event rising\u{201D}"),
+                   Ok(Some((long("kind", Some("event rising")), ""))));
+        assert_eq!(named_arg("--kind="), Ok(Some((long("kind", Some("")), ""))));
+        assert_eq!(named_arg("-u -k x"), Ok(Some((Named::Short('u'), "-k x"))));
+    }
+
+    #[test]
+    fn test_not_named_arg() {
+        assert_eq!(named_arg("--"), Ok(None));
+        assert_eq!(named_arg("-- --x"), Ok(None));
+        assert_eq!(named_arg("-5"), Ok(None));
+        assert_eq!(named_arg("-"), Ok(None));
+        assert_eq!(named_arg("-ab"), Ok(None));
+        assert_eq!(named_arg("word"), Ok(None));
     }
 }
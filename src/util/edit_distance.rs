@@ -0,0 +1,50 @@
+use std::cmp;
+
+/// The Levenshtein distance between `a` and `b`, counted in `char`s.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..b_chars.len() + 1).collect();
+    let mut current = vec![0; b_chars.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution_cost = if a_char == *b_char { 0 } else { 1 };
+            current[j + 1] = cmp::min(cmp::min(current[j] + 1, previous[j + 1] + 1),
+                                      previous[j] + substitution_cost);
+        }
+        previous.clone_from_slice(&current);
+    }
+
+    previous[b_chars.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levenshtein_identical() {
+        assert_eq!(levenshtein("ping", "ping"), 0);
+        assert_eq!(levenshtein("", ""), 0);
+    }
+
+    #[test]
+    fn levenshtein_against_empty() {
+        assert_eq!(levenshtein("", "roll"), 4);
+        assert_eq!(levenshtein("roll", ""), 4);
+    }
+
+    #[test]
+    fn levenshtein_single_edits() {
+        assert_eq!(levenshtein("pign", "ping"), 2);
+        assert_eq!(levenshtein("rol", "roll"), 1);
+        assert_eq!(levenshtein("rolls", "roll"), 1);
+        assert_eq!(levenshtein("rall", "roll"), 1);
+    }
+
+    #[test]
+    fn levenshtein_counts_chars_not_bytes() {
+        assert_eq!(levenshtein("café", "cafe"), 1);
+    }
+}
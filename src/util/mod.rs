@@ -1 +1,9 @@
 pub mod arg_parser;
+pub mod edit_distance;
+
+use std::time::Duration;
+
+/// `d` in whole milliseconds.
+pub fn duration_ms(d: Duration) -> u64 {
+    d.as_secs().saturating_mul(1000) + (d.subsec_nanos() / 1_000_000) as u64
+}
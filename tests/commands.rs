@@ -0,0 +1,339 @@
+extern crate discord;
+extern crate technobot;
+
+use discord::model::{ChannelId, MessageId, Permissions, ServerId, UserId, permissions};
+use std::sync::Arc;
+use std::time::Duration;
+use technobot::commands;
+use technobot::config::Config;
+use technobot::framework::dispatcher::Dispatcher;
+use technobot::framework::entities::Directory;
+use technobot::framework::mock::{MockResponder, Sent};
+use technobot::framework::permissions::ServerCaller;
+use technobot::framework::prefixes::Prefixes;
+use technobot::framework::registry::CommandRegistry;
+use technobot::framework::shutdown::ShutdownKind;
+use technobot::framework::transport::{Author, IncomingMessage};
+
+const BOT: UserId = UserId(1);
+const OWNER: UserId = UserId(2);
+const USER: UserId = UserId(3);
+const CHANNEL: ChannelId = ChannelId(10);
+const SERVER: ServerId = ServerId(20);
+
+/// A dispatcher with every command registered, and a mock to capture its replies.
+struct Harness {
+    dispatcher: Dispatcher,
+    responder: Arc<MockResponder>,
+}
+
+impl Harness {
+    fn new() -> Harness {
+        let mut registry = CommandRegistry::new();
+        commands::register_all(&mut registry, &Config::default());
+
+        Harness {
+            dispatcher: Dispatcher::new(registry, Prefixes::new("!"), vec![OWNER]),
+            responder: Arc::new(MockResponder::new(BOT)),
+        }
+    }
+
+    /// Send `content` as `author` in a server channel, where they have
+    /// `permissions`, and return what the bot sent back.
+    fn send_as(&self, author: UserId, permissions: Permissions, content: &str) -> Vec<Sent> {
+        let message = message(author, permissions, content);
+        self.dispatcher.dispatch(self.responder.clone(), &message);
+        self.responder.take_sent()
+    }
+
+    fn send(&self, content: &str) -> Vec<Sent> {
+        self.send_as(USER, Permissions::empty(), content)
+    }
+
+    /// Edit the message last sent to say `content`, and return what the bot
+    /// sent or edited in response.
+    fn edit(&self, content: &str) -> Vec<Sent> {
+        let message = message(USER, Permissions::empty(), content);
+        self.dispatcher.dispatch_edit(self.responder.clone(), &message);
+        self.responder.take_sent()
+    }
+
+    /// The text of the single message sent in reply to `content`.
+    fn text_reply(&self, content: &str) -> String {
+        let sent = self.send(content);
+        assert_eq!(sent.len(), 1, "expected one reply to {:?}, got {:?}", content, sent);
+        match sent.into_iter().next().unwrap() {
+            Sent::Message { channel, text } => {
+                assert_eq!(channel, CHANNEL);
+                text
+            }
+            other => panic!("expected a text reply to {:?}, got {:?}", content, other),
+        }
+    }
+}
+
+/// A message from `author` in a server channel, where they have `permissions`.
+/// Every message has the same ID, so each one can be edited into the next.
+fn message(author: UserId, permissions: Permissions, content: &str) -> IncomingMessage {
+    IncomingMessage {
+        id: MessageId(100),
+        channel_id: CHANNEL,
+        server_id: Some(SERVER),
+        author: Author {
+            id: author,
+            name: "someone".to_string(),
+        },
+        member: Some(ServerCaller {
+                         is_server_owner: false,
+                         permissions: permissions,
+                         role_names: vec![],
+                     }),
+        directory: Some(Directory::default()),
+        content: content.to_string(),
+    }
+}
+
+#[test]
+fn ping() {
+    let harness = Harness::new();
+
+    assert_eq!(harness.text_reply("!ping"), "Pong!");
+}
+
+#[test]
+fn ignores_messages_without_prefix() {
+    let harness = Harness::new();
+
+    assert!(harness.send("ping").is_empty());
+    assert!(harness.send("?ping").is_empty());
+}
+
+#[test]
+fn responds_to_mentions() {
+    let harness = Harness::new();
+
+    assert_eq!(harness.text_reply("<@1> ping"), "Pong!");
+    assert_eq!(harness.text_reply("<@!1> ping"), "Pong!");
+}
+
+#[test]
+fn mentions_in_conversation_get_no_suggestions() {
+    let harness = Harness::new();
+
+    assert!(harness.send("<@1> pnig").is_empty());
+    assert!(harness.send("<@1> thanks!").is_empty());
+}
+
+#[test]
+fn roll() {
+    let harness = Harness::new();
+
+    assert_eq!(harness.text_reply("!roll 3d1"), "<@3>: Rolled 3d1 and got 3");
+}
+
+#[test]
+fn roll_bad_arguments() {
+    let harness = Harness::new();
+
+    let reply = harness.text_reply("!roll 2d2O");
+    assert!(reply.contains("Usage: `!roll [dice]`"), "got {:?}", reply);
+}
+
+#[test]
+fn editing_reruns_and_edits_the_reply() {
+    let harness = Harness::new();
+    let first = harness.send("!roll 2d2O");
+    assert_eq!(first.len(), 1);
+
+    assert_eq!(harness.edit("!roll 3d1"),
+               vec![Sent::Edit {
+                        channel: CHANNEL,
+                        message: MessageId(1),
+                        text: "<@3>: Rolled 3d1 and got 3".to_string(),
+                    }]);
+}
+
+#[test]
+fn editing_deletes_replies_no_longer_needed() {
+    let harness = Harness::new();
+    harness.send("!roll 2d2O");
+
+    let sent = harness.edit("!help");
+    assert_eq!(sent.len(), 2, "got {:?}", sent);
+    match sent[0] {
+        Sent::Embed { .. } => {}
+        ref other => panic!("expected a new embed, got {:?}", other),
+    }
+    assert_eq!(sent[1],
+               Sent::Delete {
+                   channel: CHANNEL,
+                   message: MessageId(1),
+               });
+}
+
+#[test]
+fn updates_without_new_content_do_nothing() {
+    let harness = Harness::new();
+    harness.send("!roll 3d1");
+
+    assert_eq!(harness.edit("!roll 3d1"), vec![]);
+}
+
+#[test]
+fn editing_unknown_messages_does_nothing() {
+    let harness = Harness::new();
+
+    assert_eq!(harness.edit("!ping"), vec![]);
+}
+
+#[test]
+fn usage_errors_name_the_argument() {
+    let harness = Harness::new();
+
+    let sent = harness.send_as(USER, permissions::ADMINISTRATOR, "!prefix set");
+    assert_eq!(sent,
+               vec![Sent::Message {
+                        channel: CHANNEL,
+                        text: "Missing `prefix`. Usage: `!prefix set <prefix>`".to_string(),
+                    }]);
+    let reply = harness.text_reply("!roll 2d0");
+    assert!(reply.starts_with("Invalid `dice`: Dice need at least one side."),
+            "got {:?}",
+            reply);
+}
+
+#[test]
+fn parse_errors_point_at_the_problem() {
+    let harness = Harness::new();
+
+    assert_eq!(harness.text_reply("!roll \"2d6"),
+               "This \" is never closed. Usage: `!roll [dice]`\n```\n\"2d6\n^\n```");
+    assert_eq!(harness.text_reply("!roll 2d6 extra"),
+               "Unexpected `extra`. Usage: `!roll [dice]`\n```\n2d6 extra\n    ^\n```");
+}
+
+#[test]
+fn commands_are_counted_by_outcome() {
+    let harness = Harness::new();
+
+    harness.send("!ping");
+    harness.send("!roll 2d2O");
+    harness.send("!pnig");
+
+    let metrics = harness.dispatcher.metrics();
+    assert_eq!(metrics.commands_run("ping", "ok"), 1);
+    assert_eq!(metrics.commands_run("roll", "bad_arguments"), 1);
+    assert!(!metrics.render(&Default::default()).contains("pnig"));
+}
+
+#[test]
+fn eight_ball() {
+    let harness = Harness::new();
+
+    assert!(!harness.text_reply("!8-ball Will this test pass?").is_empty());
+}
+
+#[test]
+fn ffxiv_resets() {
+    let harness = Harness::new();
+
+    let sent = harness.send("!ffxiv resets");
+    assert_eq!(sent.len(), 1);
+    match sent[0] {
+        Sent::Embed { ref embed, .. } => {
+            assert_eq!(embed.title, Some("FF XIV Resets".to_string()));
+            let names: Vec<&str> = embed.fields.iter().map(|f| f.name.as_str()).collect();
+            assert_eq!(names, ["Daily", "Weekly", "Crafting"]);
+        }
+        ref other => panic!("expected an embed, got {:?}", other),
+    }
+}
+
+#[test]
+fn help_is_one_embed() {
+    let harness = Harness::new();
+
+    let sent = harness.send("!help");
+    assert_eq!(sent.len(), 1);
+    match sent[0] {
+        Sent::Embed { ref embed, .. } => {
+            assert_eq!(embed.title, Some("Commands".to_string()));
+            assert!(embed.fields.iter().any(|f| f.name == "!ffxiv"));
+        }
+        ref other => panic!("expected an embed, got {:?}", other),
+    }
+}
+
+#[test]
+fn help_pages_past_the_end() {
+    let harness = Harness::new();
+
+    let reply = harness.text_reply("!help 9");
+    assert!(reply.starts_with("There's only 1 page of commands."), "got {:?}", reply);
+}
+
+#[test]
+fn suggests_mistyped_commands() {
+    let harness = Harness::new();
+
+    assert_eq!(harness.text_reply("!pnig"),
+               "Unknown command. Did you mean `!ping`?");
+    assert_eq!(harness.text_reply("!ffxiv restes"),
+               "Unknown command. Did you mean `!ffxiv resets`?");
+}
+
+#[test]
+fn shutdown_requires_owner() {
+    let harness = Harness::new();
+
+    let reply = harness.text_reply("!shutdown");
+    assert!(reply.starts_with("You can't use `!shutdown`"), "got {:?}", reply);
+    assert!(!harness.dispatcher.shutdown().is_requested());
+
+    harness.send_as(OWNER, Permissions::empty(), "!quit");
+    assert!(harness.dispatcher.shutdown().is_requested());
+}
+
+#[test]
+fn restart_confirms_once_finished() {
+    let harness = Harness::new();
+
+    assert!(harness.send_as(OWNER, Permissions::empty(), "!restart").is_empty());
+    // Nothing new runs while shutting down.
+    assert!(harness.send("!ping").is_empty());
+
+    let request = harness.dispatcher.shutdown().wait();
+    assert_eq!(request.kind, ShutdownKind::Restart);
+    harness.dispatcher.finish_shutdown(&request, Duration::from_secs(1));
+    assert_eq!(harness.responder.take_sent(),
+               vec![Sent::Message {
+                        channel: CHANNEL,
+                        text: "Restarting.".to_string(),
+                    }]);
+}
+
+#[test]
+fn prefix_set_by_admin() {
+    let harness = Harness::new();
+
+    let sent = harness.send_as(USER, permissions::ADMINISTRATOR, "!prefix set ?");
+    assert_eq!(sent,
+               vec![Sent::Message {
+                        channel: CHANNEL,
+                        text: "The command prefix is now `?`.".to_string(),
+                    }]);
+    assert_eq!(harness.text_reply("?ping"), "Pong!");
+    assert!(harness.send("!ping").is_empty());
+}
+
+#[test]
+fn shards_without_discord() {
+    let harness = Harness::new();
+
+    let sent = harness.send_as(OWNER, Permissions::empty(), "!shards");
+    assert_eq!(sent,
+               vec![Sent::Message {
+                        channel: CHANNEL,
+                        text: "Not connected to Discord.".to_string(),
+                    }]);
+}